@@ -7,7 +7,15 @@ use clap::{ArgAction, Args as ClapArgs, Parser, Subcommand, ValueEnum, ValueHint
 
 use clap_complete::{generate, Shell};
 
-use lazy_static::lazy_static;
+use crate::check::Threshold;
+
+use dfrs::column::ColumnType;
+use dfrs::config::Config;
+use dfrs::expr::Expr;
+use dfrs::filter::DisplayFilter;
+use dfrs::util;
+
+use glob::Pattern;
 use std::path::PathBuf;
 use strum_macros::{Display, EnumString, VariantNames};
 
@@ -17,14 +25,17 @@ use strum_macros::{Display, EnumString, VariantNames};
 #[command(disable_help_flag = true)]
 pub struct Args {
     /// Show more, use twice to show all
-    #[arg(short = 'a', group = "display_group", action = ArgAction::Count, )]
+    #[arg(global = true, short = 'a', group = "display_group", action = ArgAction::Count)]
     pub display: u8,
     /// Show more
-    #[arg(long, group = "display_group")]
+    #[arg(global = true, long, group = "display_group")]
     pub more: bool,
     /// Show all
-    #[arg(long, group = "display_group")]
+    #[arg(global = true, long, group = "display_group")]
     pub all: bool,
+    /// Show only the regular file systems, whatever the configuration says
+    #[arg(global = true, long, group = "display_group")]
+    pub minimal: bool,
     /// Bypass tty detection for colors
     #[arg(long, group = "color_group")]
     pub color: Option<ColorOpt>,
@@ -40,26 +51,119 @@ pub struct Args {
     /// Print sizes in powers of 1000 (e.g., 1.1G)
     #[arg(short = 'H', long = "si", group = "number_format")]
     pub base10: bool,
+    /// Print sizes as plain numbers of bytes
+    #[arg(long, group = "number_format")]
+    pub raw_numbers: bool,
+    /// Print sizes in blocks of SIZE bytes, rounded up (e.g., K, 1M, 4KB, 512)
+    #[arg(short = 'B', long, value_name = "SIZE", group = "number_format", value_parser = parse_block_size)]
+    pub block_size: Option<u64>,
+    /// Print sizes in powers of 1024 with KiB, MiB, ... suffixes
+    #[arg(long, conflicts_with_all = ["base10", "raw_numbers", "block_size"])]
+    pub iec: bool,
+    /// Digits after the decimal point of sizes in powers of 1024 or 1000
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub precision: usize,
+    /// Group the thousands of plain numbers, by SEP or the separator of the locale
+    #[arg(long, value_name = "SEP", num_args = 0..=1, require_equals = true, default_missing_value = "", value_parser = parse_separator)]
+    pub thousands: Option<String>,
+    /// Color rows by the higher of the block and the inode usage
+    #[arg(global = true, long)]
+    pub color_by_worse: bool,
+    /// Count the space reserved for root as used, percentages are then relative to the total size
+    #[arg(global = true, long)]
+    pub reserved_as_used: bool,
     /// Produce and show a grand total
     #[arg(long)]
     pub total: bool,
     /// Limit listing to local file systems
-    #[arg(short, long)]
+    #[arg(global = true, short, long, overrides_with = "no_local")]
     pub local: bool,
+    /// List remote file systems too, even if the configuration limits to local ones
+    #[arg(global = true, long, overrides_with = "local")]
+    pub no_local: bool,
+    /// Only show file systems of this type, may be given more than once
+    #[arg(global = true, short = 't', long = "type", value_name = "TYPE")]
+    pub types: Vec<String>,
+    /// Hide file systems of this type, may be given more than once
+    #[arg(global = true, short = 'x', long = "exclude-type", value_name = "TYPE")]
+    pub exclude_types: Vec<String>,
+    /// Only show mount points matching the glob pattern (e.g., '/srv/**')
+    #[arg(global = true, long = "include-mount", value_name = "GLOB")]
+    pub include_mounts: Vec<Pattern>,
+    /// Hide mount points matching the glob pattern (e.g., '/var/lib/kubelet/**')
+    #[arg(global = true, long = "exclude-mount", value_name = "GLOB")]
+    pub exclude_mounts: Vec<Pattern>,
+    /// Only show file systems whose name matches the glob pattern (e.g., '/dev/sd*')
+    #[arg(global = true, long = "device", value_name = "GLOB")]
+    pub devices: Vec<Pattern>,
+    /// Only show file systems matching the expression (e.g., 'used_percentage > 80 && !opt.ro')
+    #[arg(global = true, long = "where", value_name = "EXPR")]
+    pub predicate: Option<Expr>,
+    /// Ignore the type, mount point, device and expression filters of the configuration
+    #[arg(global = true, long)]
+    pub no_config_filters: bool,
     /// Do not resolve file system shorthand aliases (e.g., LVM)
     #[arg(long)]
     pub no_aliases: bool,
     /// File to get mount information from
-    #[arg(long, value_hint = ValueHint::FilePath, default_value = "/proc/self/mounts", value_name = "FILE")]
+    #[arg(global = true, long, value_hint = ValueHint::FilePath, default_value = "/proc/self/mountinfo", value_name = "FILE")]
     pub mounts: PathBuf,
+    /// Seconds to wait for the usage of a file system before marking it stale, 0 waits indefinitely
+    #[arg(global = true, long, value_name = "SECONDS", default_value_t = 5.0, value_parser = parse_timeout)]
+    pub timeout: f64,
+    /// Show a snapshot written by --output json instead of the live system
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "FILE")]
+    pub input: Option<PathBuf>,
+    /// Apply the settings of this profile of the configuration file
+    #[arg(global = true, long, value_name = "NAME")]
+    pub profile: Option<String>,
+    /// Behave like GNU df, as when invoked as df; has to be the first argument
+    #[arg(long)]
+    pub df_compat: bool,
     /// Verbose logging
-    #[arg(short)]
+    #[arg(global = true, short)]
     pub verbose: bool,
     #[arg(value_hint = ValueHint::AnyPath)]
     pub paths: Vec<PathBuf>,
-    /// Display columns as comma separated list
-    #[arg(long, use_value_delimiter = true, default_value = &**COLUMNS_OPT_DEFAULT_VALUE)]
-    pub columns: Vec<ColumnType>,
+    /// Display columns as comma separated list [default: filesystem,type,bar,used_percentage,available,used,capacity,mounted_on]
+    #[arg(long, use_value_delimiter = true)]
+    pub columns: Option<Vec<ColumnType>>,
+    /// Fit the table into N characters instead of the width of the terminal
+    #[arg(global = true, long, value_name = "N", conflicts_with = "no_truncate")]
+    pub width: Option<usize>,
+    /// Show the table in full even if it is wider than the terminal
+    #[arg(global = true, long)]
+    pub no_truncate: bool,
+    /// Sort by columns as comma separated list, later columns break ties
+    #[arg(long, use_value_delimiter = true, value_name = "COLUMNS")]
+    pub sort: Option<Vec<ColumnType>>,
+    /// Reverse the order
+    #[arg(long)]
+    pub reverse: bool,
+    /// Show file systems mounted several times, like bind mounts, once with all their mount points
+    #[arg(global = true, long, overrides_with = "no_dedupe")]
+    pub dedupe: bool,
+    /// Show each mount of a file system, even if the configuration dedupes them
+    #[arg(global = true, long, overrides_with = "dedupe")]
+    pub no_dedupe: bool,
+    /// List every mount with the rule that shows or hides it
+    #[arg(long)]
+    pub explain: bool,
+    /// Only show file systems whose usage could not be queried, with the reason
+    #[arg(long)]
+    pub errors: bool,
+    /// Exit with an error if the usage of any shown file system could not be queried
+    #[arg(long)]
+    pub strict: bool,
+    /// Only show the N fullest file systems
+    #[arg(long, value_name = "N")]
+    pub top: Option<usize>,
+    /// Output format
+    #[arg(long, value_name = "FORMAT", default_value_t = OutputFormat::Table)]
+    pub output: OutputFormat,
+    /// Atomically write the output to a file instead of stdout
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    pub output_file: Option<PathBuf>,
     /// Print help information
     #[arg(long, action = ArgAction::Help, global = true)]
     pub help: Option<bool>,
@@ -72,109 +176,188 @@ pub enum SubCommand {
     /// Generate shell completions
     #[clap(name = "completions")]
     Completions(Completions),
+    /// Print the JSON Schema of the json and ndjson output
+    #[clap(name = "schema")]
+    Schema,
+    /// Check usage against thresholds as a Nagios/Icinga plugin
+    #[clap(name = "check")]
+    Check(CheckArgs),
+    /// Redraw the usage periodically with changes, rates and time to full
+    #[clap(name = "watch")]
+    Watch(WatchArgs),
+    /// Append the current usage to the history store
+    #[clap(name = "record")]
+    Record(RecordArgs),
+    /// Project when file systems fill up from the recorded history
+    #[clap(name = "forecast")]
+    Forecast(ForecastArgs),
+    /// Compare the usage of two snapshots or a snapshot and the live system
+    #[clap(name = "diff")]
+    Diff(DiffArgs),
+    /// Merge the snapshots of several hosts into one table
+    #[clap(name = "fleet")]
+    Fleet(FleetArgs),
+    /// Inspect the configuration files
+    #[clap(name = "config")]
+    Config(ConfigArgs),
 }
 
-#[derive(Debug, Clone, ValueEnum, Display, EnumString, VariantNames)]
-#[strum(serialize_all = "lowercase")]
-pub enum ColorOpt {
-    Auto,
-    Always,
-    Never,
+#[derive(Debug, ClapArgs)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
 }
 
-#[derive(Debug, Clone, ValueEnum, EnumString)]
-#[strum(serialize_all = "lowercase")]
-pub enum DisplayFilter {
-    Minimal,
-    More,
-    All,
-}
-
-impl DisplayFilter {
-    pub const fn from_u8(n: u8) -> Self {
-        match n {
-            0 => Self::Minimal,
-            1 => Self::More,
-            _ => Self::All,
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print the effective configuration and where each value comes from
+    #[clap(name = "show")]
+    Show,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct FleetArgs {
+    /// Only show file systems with at least this used percentage
+    #[arg(long, value_name = "PERCENT")]
+    pub min_used: Option<f32>,
+    /// Snapshots written by --output json
+    #[arg(required = true, value_name = "SNAPSHOT", value_hint = ValueHint::FilePath)]
+    pub snapshots: Vec<PathBuf>,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct DiffArgs {
+    /// Snapshot written by --output json, or "live" for the current usage
+    #[arg(value_name = "OLD", value_hint = ValueHint::FilePath)]
+    pub old: Source,
+    /// Snapshot written by --output json, or "live" for the current usage
+    #[arg(value_name = "NEW", value_hint = ValueHint::FilePath, default_value = "live")]
+    pub new: Source,
+    /// Growth in percentage points highlighted as a significant change
+    #[arg(long, value_name = "PERCENT", default_value_t = 5.0)]
+    pub significant: f32,
+}
+
+/// Where to take the mounts and their usage from
+#[derive(Debug, Clone)]
+pub enum Source {
+    Live,
+    Snapshot(PathBuf),
+}
+
+impl From<&str> for Source {
+    fn from(s: &str) -> Self {
+        match s {
+            "live" => Self::Live,
+            path => Self::Snapshot(PathBuf::from(path)),
         }
     }
+}
 
-    pub fn get_mnt_fsname_filter(&self) -> Vec<&'static str> {
-        match self {
-            Self::Minimal => vec!["/dev*", "storage"],
-            Self::More => vec!["dev", "run", "tmpfs", "/dev*", "storage"],
-            Self::All => vec!["*"],
-        }
+#[derive(Debug, ClapArgs)]
+pub struct RecordArgs {
+    /// History file [default: $XDG_STATE_HOME/dfrs/history]
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "FILE")]
+    pub history: Option<PathBuf>,
+    /// Drop samples older than this many days, 0 keeps them forever
+    #[arg(long, value_name = "DAYS", default_value_t = 365)]
+    pub retention: u64,
+    #[arg(value_hint = ValueHint::AnyPath)]
+    pub paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ForecastArgs {
+    /// History file [default: $XDG_STATE_HOME/dfrs/history]
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "FILE")]
+    pub history: Option<PathBuf>,
+    /// Only forecast the file systems of these paths
+    #[arg(value_hint = ValueHint::AnyPath)]
+    pub paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct WatchArgs {
+    /// Seconds to wait between updates
+    #[arg(short = 'n', long, value_name = "SECONDS", default_value_t = 2.0, value_parser = parse_interval)]
+    pub interval: f64,
+    #[arg(value_hint = ValueHint::AnyPath)]
+    pub paths: Vec<PathBuf>,
+}
+
+fn parse_interval(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(interval) if interval >= 0.1 && interval.is_finite() => Ok(interval),
+        _ => Err("must be a number of seconds of at least 0.1".to_string()),
     }
 }
 
-#[derive(Debug)]
-pub enum NumberFormat {
-    Base10,
-    Base2,
+fn parse_block_size(s: &str) -> Result<u64, String> {
+    util::parse_block_size(s).map_err(|err| err.to_string())
 }
 
-impl NumberFormat {
-    pub const fn get_powers_of(&self) -> f64 {
-        match self {
-            Self::Base10 => 1000_f64,
-            Self::Base2 => 1024_f64,
-        }
+fn parse_separator(s: &str) -> Result<String, String> {
+    match s.chars().count() {
+        0 | 1 => Ok(s.to_string()),
+        _ => Err("must be a single character".to_string()),
     }
 }
 
-#[derive(Debug, Clone, Display, ValueEnum, EnumString, VariantNames)]
-#[strum(serialize_all = "snake_case")]
-#[clap(rename_all = "snake_case")]
-pub enum ColumnType {
-    Filesystem,
-    Type,
-    Bar,
-    Used,
-    UsedPercentage,
-    Available,
-    AvailablePercentage,
-    Capacity,
-    MountedOn,
-}
-
-impl ColumnType {
-    pub const fn label(&self, inodes_mode: bool) -> &str {
-        match self {
-            Self::Filesystem => "Filesystem",
-            Self::Type => "Type",
-            Self::Bar => "",
-            Self::Used => "Used",
-            Self::UsedPercentage => "Used%",
-            Self::Available => "Avail",
-            Self::AvailablePercentage => "Avail%",
-            Self::Capacity => {
-                if inodes_mode {
-                    "Inodes"
-                } else {
-                    "Size"
-                }
-            }
-            Self::MountedOn => "Mounted on",
-        }
+fn parse_timeout(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(timeout) if timeout >= 0.0 && timeout.is_finite() => Ok(timeout),
+        _ => Err("must be a non-negative number of seconds".to_string()),
     }
 }
 
-lazy_static! {
-    static ref COLUMNS_OPT_DEFAULT_VALUE: String = [
-        ColumnType::Filesystem,
-        ColumnType::Type,
-        ColumnType::Bar,
-        ColumnType::UsedPercentage,
-        ColumnType::Available,
-        ColumnType::Used,
-        ColumnType::Capacity,
-        ColumnType::MountedOn
-    ]
-    .iter()
-    .map(|e| e.to_string())
-    .collect::<Vec<String>>()
-    .join(",");
+#[derive(Debug, ClapArgs)]
+pub struct CheckArgs {
+    /// Warn at a used percentage (e.g., 80%) or below a free size (e.g., 10G)
+    #[arg(short, long, value_name = "THRESHOLD", default_value = "80%")]
+    pub warning: Threshold,
+    /// Critical at a used percentage (e.g., 90%) or below a free size (e.g., 5G)
+    #[arg(short, long, value_name = "THRESHOLD", default_value = "90%")]
+    pub critical: Threshold,
+    /// Warn at a used inode percentage or below a free inode count
+    #[arg(
+        short = 'W',
+        long,
+        value_name = "THRESHOLD",
+        value_parser = Threshold::parse_count,
+        requires = "inode_critical"
+    )]
+    pub inode_warning: Option<Threshold>,
+    /// Critical at a used inode percentage or below a free inode count
+    #[arg(
+        short = 'K',
+        long,
+        value_name = "THRESHOLD",
+        value_parser = Threshold::parse_count,
+        requires = "inode_warning"
+    )]
+    pub inode_critical: Option<Threshold>,
+    #[arg(value_hint = ValueHint::AnyPath)]
+    pub paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, ValueEnum, Display, EnumString, VariantNames)]
+#[strum(serialize_all = "lowercase")]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Ndjson,
+    Csv,
+    Tsv,
+    Prometheus,
+    Openmetrics,
+}
+
+#[derive(Debug, Clone, ValueEnum, Display, EnumString, VariantNames)]
+#[strum(serialize_all = "lowercase")]
+pub enum ColorOpt {
+    Auto,
+    Always,
+    Never,
 }
 
 #[derive(Debug, ClapArgs)]
@@ -182,8 +365,96 @@ pub struct Completions {
     pub shell: Shell,
 }
 
+impl Args {
+    /// Take the settings not given on the command line from the configuration
+    pub fn merge_config(&mut self, config: &Config) {
+        if self.display == 0 && !self.more && !self.all && !self.minimal {
+            match config.display_filter() {
+                DisplayFilter::Minimal => {}
+                DisplayFilter::More => self.more = true,
+                DisplayFilter::All => self.all = true,
+            }
+        }
+        if !self.no_local {
+            self.local |= config.local_only();
+        }
+        if !self.no_dedupe {
+            self.dedupe |= config.dedupe();
+        }
+        if self.no_config_filters {
+            return;
+        }
+        let filter = config.mount_filter();
+        fn or_config<T>(args: &mut Vec<T>, config: Vec<T>) {
+            if args.is_empty() {
+                *args = config;
+            }
+        }
+        or_config(&mut self.types, filter.types);
+        or_config(&mut self.exclude_types, filter.exclude_types);
+        or_config(&mut self.include_mounts, filter.include_mounts);
+        or_config(&mut self.exclude_mounts, filter.exclude_mounts);
+        or_config(&mut self.devices, filter.devices);
+        if self.predicate.is_none() {
+            self.predicate = config.predicate();
+        }
+    }
+}
+
 pub fn gen_completions(completions: &Completions) {
     let mut cmd = Args::command();
     let bin_name = cmd.get_name().to_string();
     generate(completions.shell, &mut cmd, &bin_name, &mut stdout());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn merged(config: &str, argv: &[&str]) -> Args {
+        let path = env::temp_dir().join(format!(
+            "dfrs-args-{}-{}.toml",
+            argv.join(""),
+            std::process::id()
+        ));
+        fs::write(&path, config).unwrap();
+        let config = Config::load_paths(&[&path], None);
+        fs::remove_file(&path).unwrap();
+        let mut args = Args::parse_from(std::iter::once("dfrs").chain(argv.iter().copied()));
+        args.merge_config(&config.unwrap());
+        args
+    }
+
+    #[test]
+    fn command_line_overrides_config() {
+        let config = "[filters]\n\
+                      display = \"all\"\n\
+                      local = true\n\
+                      dedupe = true\n\
+                      types = [\"ext4\"]\n\
+                      where = \"used_percentage > 80\"\n";
+
+        let args = merged(config, &[]);
+        assert!(args.all && args.local && args.dedupe);
+        assert_eq!(args.types, ["ext4"]);
+        assert!(args.predicate.is_some());
+
+        let args = merged(
+            config,
+            &[
+                "--minimal",
+                "--no-local",
+                "--no-dedupe",
+                "--no-config-filters",
+            ],
+        );
+        assert!(!args.all && !args.more && !args.local && !args.dedupe);
+        assert!(args.types.is_empty());
+        assert!(args.predicate.is_none());
+
+        let args = merged("", &["--no-local", "--local", "--dedupe", "--no-dedupe"]);
+        assert!(args.local && !args.dedupe);
+    }
+}
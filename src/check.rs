@@ -0,0 +1,538 @@
+use crate::args::CheckArgs;
+
+use dfrs::util::{parse_size, NumberFormat};
+use dfrs::Mount;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Service states and exit codes as defined by the Nagios plugin API
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+    Unknown = 3,
+}
+
+impl State {
+    pub const fn exit_code(self) -> i32 {
+        self as i32
+    }
+
+    /// Rank when combining the states of several checks, like the
+    /// monitoring-plugins an unknown one does not hide a problem elsewhere
+    const fn severity(self) -> u8 {
+        match self {
+            Self::Ok => 0,
+            Self::Unknown => 1,
+            Self::Warning => 2,
+            Self::Critical => 3,
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ok => "OK",
+            Self::Warning => "WARNING",
+            Self::Critical => "CRITICAL",
+            Self::Unknown => "UNKNOWN",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threshold {
+    /// Exceeded when the used percentage reaches the value (e.g., `80%`)
+    UsedPercentage(f32),
+    /// Exceeded when less than the given amount is free (e.g., `10G`)
+    MinFree(u64),
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with(s, |s| {
+            // a bare number is a percentage out of habit, not a byte count
+            if s.trim().ends_with(|c: char| c.is_ascii_digit()) {
+                return Err(format!(
+                    "Threshold {} needs a unit like 10G or a percentage like {}%",
+                    s,
+                    s.trim()
+                ));
+            }
+            parse_size(s).map_err(|err| err.to_string())
+        })
+    }
+}
+
+impl Threshold {
+    /// Parse an inode threshold, whose minimum free amount is a plain count
+    pub fn parse_count(s: &str) -> Result<Self, String> {
+        Self::parse_with(s, |s| {
+            s.trim()
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid count {}", s))
+        })
+    }
+
+    fn parse_with<F>(s: &str, amount: F) -> Result<Self, String>
+    where
+        F: Fn(&str) -> Result<u64, String>,
+    {
+        if let Some(percentage) = s.strip_suffix('%') {
+            return percentage
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|p| (0.0..=100.0).contains(p))
+                .map(Self::UsedPercentage)
+                .ok_or_else(|| format!("Invalid percentage {}", s));
+        }
+        amount(s).map(Self::MinFree)
+    }
+
+    /// Whether this warning threshold is only exceeded after the critical
+    /// one, thresholds of different kinds are not comparable
+    fn looser_than(&self, critical: &Self) -> bool {
+        match (self, critical) {
+            (Self::UsedPercentage(warning), Self::UsedPercentage(critical)) => warning > critical,
+            (Self::MinFree(warning), Self::MinFree(critical)) => warning < critical,
+            _ => false,
+        }
+    }
+
+    fn exceeded(&self, usage: &Usage) -> bool {
+        match self {
+            Self::UsedPercentage(p) => usage.used_percentage().is_some_and(|used| used >= *p),
+            Self::MinFree(free) => usage.available < *free,
+        }
+    }
+
+    /// Threshold expressed in the unit of the perfdata value
+    fn perf_value(&self, usage: &Usage, percent: bool) -> String {
+        match (self, percent) {
+            (Self::UsedPercentage(p), true) => format!("{}", p),
+            (Self::UsedPercentage(p), false) => {
+                format!("{}", (usage.total() as f64 * f64::from(*p) / 100.0).round())
+            }
+            (Self::MinFree(free), _) => format!("{}", usage.total().saturating_sub(*free)),
+        }
+    }
+}
+
+/// Usage as shown in the table, the used percentage is relative to the
+/// used and available amount like GNU df's
+#[derive(Debug, Clone, Copy)]
+struct Usage {
+    capacity: u64,
+    used: u64,
+    available: u64,
+    used_percentage: Option<f32>,
+}
+
+impl Usage {
+    fn blocks(mnt: &Mount) -> Self {
+        Self {
+            capacity: mnt.capacity,
+            used: mnt.used,
+            available: mnt.available,
+            used_percentage: mnt.used_percentage(),
+        }
+    }
+
+    fn inodes(mnt: &Mount) -> Self {
+        Self {
+            capacity: mnt.inodes().unwrap_or(0),
+            used: mnt.inodes_used().unwrap_or(0),
+            available: mnt.inodes_free().unwrap_or(0),
+            used_percentage: mnt.inodes_used_percentage(),
+        }
+    }
+
+    const fn used_percentage(&self) -> Option<f32> {
+        self.used_percentage
+    }
+
+    /// The amount the used percentage is relative to
+    const fn total(&self) -> u64 {
+        self.used + self.available
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Blocks,
+    Inodes,
+}
+
+struct Check<'a> {
+    mnt: &'a Mount,
+    kind: Kind,
+    usage: Usage,
+    warning: Threshold,
+    critical: Threshold,
+}
+
+impl Check<'_> {
+    fn state(&self) -> State {
+        if self.mnt.statfs.is_none() {
+            State::Unknown
+        } else if self.critical.exceeded(&self.usage) {
+            State::Critical
+        } else if self.warning.exceeded(&self.usage) {
+            State::Warning
+        } else {
+            State::Ok
+        }
+    }
+
+    fn label(&self) -> String {
+        match self.kind {
+            Kind::Blocks => self.mnt.mnt_dir.clone(),
+            Kind::Inodes => format!("{} inodes", self.mnt.mnt_dir),
+        }
+    }
+
+    fn summary(&self, format: &NumberFormat) -> String {
+        let free = match self.kind {
+            Kind::Blocks => format.format(self.usage.available),
+            Kind::Inodes => format.counts().format(self.usage.available),
+        };
+        self.usage.used_percentage().map_or_else(
+            || format!("{} no data", self.label()),
+            |used| format!("{} {:.1}% used ({} free)", self.label(), used, free),
+        )
+    }
+
+    /// Performance data as `'label'=value[UOM];warn;crit;min;max`
+    fn perfdata(&self) -> String {
+        let percent = matches!(self.warning, Threshold::UsedPercentage(_))
+            && matches!(self.critical, Threshold::UsedPercentage(_));
+        let label = self.label().replace('\'', "''");
+        let warning = self.warning.perf_value(&self.usage, percent);
+        let critical = self.critical.perf_value(&self.usage, percent);
+        if percent {
+            let used = self
+                .usage
+                .used_percentage()
+                .map_or(0.0, |p| (p * 100.0).round() / 100.0);
+            format!("'{}'={}%;{};{};0;100", label, used, warning, critical)
+        } else {
+            let used = self.usage.used;
+            let uom = match self.kind {
+                Kind::Blocks => "B",
+                Kind::Inodes => "",
+            };
+            format!(
+                "'{}'={}{};{};{};0;{}",
+                label, used, uom, warning, critical, self.usage.capacity
+            )
+        }
+    }
+}
+
+/// Evaluate the thresholds against the mounts and render the plugin output.
+///
+/// The block usage of the mounts has to be computed in bytes, `format`
+/// prints the free amounts. Mounts without any capacity, like pseudo file
+/// systems, are not checked and neither are the inodes of file systems
+/// without a fixed number of them, like btrfs.
+pub fn check_mounts(mnts: &[Mount], args: &CheckArgs, format: &NumberFormat) -> (State, String) {
+    if mnts.is_empty() {
+        return (
+            State::Unknown,
+            "DISK UNKNOWN - No file systems selected".to_string(),
+        );
+    }
+
+    let inodes = args.inode_warning.zip(args.inode_critical);
+    if args.warning.looser_than(&args.critical)
+        || inodes.is_some_and(|(warning, critical)| warning.looser_than(&critical))
+    {
+        return (
+            State::Unknown,
+            "DISK UNKNOWN - The warning threshold is looser than the critical one".to_string(),
+        );
+    }
+
+    let mut checks = Vec::new();
+    let mut checked = 0;
+    for mnt in mnts {
+        let usage = Usage::blocks(mnt);
+        if mnt.statfs.is_some() && usage.capacity == 0 {
+            continue;
+        }
+        checked += 1;
+        checks.push(Check {
+            mnt,
+            kind: Kind::Blocks,
+            usage,
+            warning: args.warning,
+            critical: args.critical,
+        });
+        if mnt.statfs.is_none() {
+            continue;
+        }
+        if let Some((warning, critical)) = inodes.filter(|_| Usage::inodes(mnt).capacity > 0) {
+            checks.push(Check {
+                mnt,
+                kind: Kind::Inodes,
+                usage: Usage::inodes(mnt),
+                warning,
+                critical,
+            });
+        }
+    }
+
+    if checks.is_empty() {
+        return (
+            State::Unknown,
+            "DISK UNKNOWN - No file systems with a capacity selected".to_string(),
+        );
+    }
+
+    let state = checks
+        .iter()
+        .map(Check::state)
+        .max_by_key(|state| state.severity())
+        .unwrap_or(State::Unknown);
+    let details = if state == State::Ok {
+        match checked {
+            1 => "1 file system OK".to_string(),
+            count => format!("{} file systems OK", count),
+        }
+    } else {
+        checks
+            .iter()
+            .filter(|check| check.state() == state)
+            .map(|check| check.summary(format))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let perfdata = checks
+        .iter()
+        .map(Check::perfdata)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    (
+        state,
+        format!("DISK {} - {} | {}", state, details, perfdata),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfrs::mount::StatfsInfo;
+    use dfrs::util::Scale;
+
+    const FORMAT: NumberFormat = NumberFormat::new(Scale::Base2);
+
+    fn mount(dir: &str, blocks: u64, blocks_available: u64) -> Mount {
+        reserved_mount(dir, blocks, blocks_available, blocks_available)
+    }
+
+    fn reserved_mount(dir: &str, blocks: u64, blocks_free: u64, blocks_available: u64) -> Mount {
+        let mut mnt = Mount::named("/dev/sda1".into());
+        mnt.mnt_dir = dir.to_string();
+        mnt.statfs = Some(StatfsInfo {
+            block_size: 1024,
+            blocks,
+            blocks_free,
+            blocks_available,
+            files: 1000,
+            files_free: 20,
+        });
+        mnt.compute_usage(false);
+        mnt
+    }
+
+    fn args(warning: &str, critical: &str) -> CheckArgs {
+        CheckArgs {
+            warning: warning.parse().unwrap(),
+            critical: critical.parse().unwrap(),
+            inode_warning: None,
+            inode_critical: None,
+            paths: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn threshold_parse() {
+        assert_eq!(
+            "80%".parse::<Threshold>().unwrap(),
+            Threshold::UsedPercentage(80.0)
+        );
+        assert_eq!(
+            "10G".parse::<Threshold>().unwrap(),
+            Threshold::MinFree(10 * 1024 * 1024 * 1024)
+        );
+        assert!("180%".parse::<Threshold>().is_err());
+        assert!("foo".parse::<Threshold>().is_err());
+        assert_eq!(
+            "80".parse::<Threshold>().unwrap_err(),
+            "Threshold 80 needs a unit like 10G or a percentage like 80%"
+        );
+
+        assert_eq!(
+            Threshold::parse_count("1000").unwrap(),
+            Threshold::MinFree(1000)
+        );
+        assert_eq!(
+            Threshold::parse_count("95%").unwrap(),
+            Threshold::UsedPercentage(95.0)
+        );
+        assert!(Threshold::parse_count("10k").is_err());
+    }
+
+    #[test]
+    fn check_rejects_loose_warning() {
+        let mnts = [mount("/", 100, 50), mount("/var", 100, 50)];
+        let (state, output) = check_mounts(&mnts, &args("90%", "80%"), &FORMAT);
+        assert_eq!(state, State::Unknown);
+        assert!(output.starts_with("DISK UNKNOWN - The warning threshold is looser"));
+        let (state, _) = check_mounts(&mnts, &args("5k", "10k"), &FORMAT);
+        assert_eq!(state, State::Unknown);
+        let (state, output) = check_mounts(&mnts, &args("10k", "90%"), &FORMAT);
+        assert_eq!(state, State::Ok);
+        assert!(output.starts_with("DISK OK - 2 file systems OK | "));
+
+        let mut args = args("80%", "90%");
+        args.inode_warning = Some(Threshold::MinFree(10));
+        args.inode_critical = Some(Threshold::MinFree(100));
+        assert_eq!(check_mounts(&mnts, &args, &FORMAT).0, State::Unknown);
+    }
+
+    #[test]
+    fn check_ok_percentage() {
+        let (state, output) = check_mounts(&[mount("/var", 100, 50)], &args("80%", "90%"), &FORMAT);
+        assert_eq!(state, State::Ok);
+        assert_eq!(
+            output,
+            "DISK OK - 1 file system OK | '/var'=50%;80;90;0;100"
+        );
+    }
+
+    #[test]
+    fn check_critical_percentage() {
+        let mnts = [
+            mount("/", 100, 50),
+            mount("/var", 100, 5),
+            mount("/home", 100, 15),
+        ];
+        let (state, output) = check_mounts(&mnts, &args("80%", "90%"), &FORMAT);
+        assert_eq!(state, State::Critical);
+        assert!(output.starts_with("DISK CRITICAL - /var 95.0% used (5.0K free) | "));
+        assert!(output.ends_with("'/home'=85%;80;90;0;100"));
+    }
+
+    #[test]
+    fn check_warning_min_free() {
+        let (state, output) = check_mounts(&[mount("/var", 100, 8)], &args("10k", "5k"), &FORMAT);
+        assert_eq!(state, State::Warning);
+        assert!(output.ends_with("| '/var'=94208B;92160;97280;0;102400"));
+    }
+
+    #[test]
+    fn check_inodes() {
+        let mut args = args("80%", "90%");
+        args.inode_warning = Some(Threshold::UsedPercentage(90.0));
+        args.inode_critical = Some(Threshold::UsedPercentage(95.0));
+        let (state, output) = check_mounts(&[mount("/var", 100, 50)], &args, &FORMAT);
+        assert_eq!(state, State::Critical);
+        assert!(output.starts_with("DISK CRITICAL - /var inodes 98.0% used (20 free) | "));
+        assert!(output.ends_with("'/var inodes'=98%;90;95;0;100"));
+    }
+
+    #[test]
+    fn check_reserved_space() {
+        // 80 used, 10 reserved and 10 available blocks
+        let mnt = reserved_mount("/var", 100, 20, 10);
+        let (state, output) =
+            check_mounts(std::slice::from_ref(&mnt), &args("80%", "90%"), &FORMAT);
+        assert_eq!(state, State::Warning);
+        assert!(output.starts_with("DISK WARNING - /var 88.9% used (10.0K free) | "));
+        assert!(output.ends_with("'/var'=88.89%;80;90;0;100"));
+
+        let (_, output) = check_mounts(std::slice::from_ref(&mnt), &args("20k", "5k"), &FORMAT);
+        assert!(output.ends_with("| '/var'=81920B;71680;87040;0;102400"));
+
+        let mut mnt = mnt;
+        mnt.count_reserved_as_used();
+        let (state, output) = check_mounts(&[mnt], &args("80%", "90%"), &FORMAT);
+        assert_eq!(state, State::Critical);
+        assert!(output.ends_with("'/var'=90%;80;90;0;100"));
+    }
+
+    #[test]
+    fn check_unknown() {
+        let mut mnt = mount("/mnt/nfs", 0, 0);
+        mnt.statfs = None;
+        let (state, _) = check_mounts(&[mount("/", 100, 50), mnt], &args("80%", "90%"), &FORMAT);
+        assert_eq!(state, State::Unknown);
+
+        let (state, _) = check_mounts(&[], &args("80%", "90%"), &FORMAT);
+        assert_eq!(state, State::Unknown);
+    }
+
+    #[test]
+    fn check_unknown_does_not_hide_critical() {
+        let mut nfs = mount("/mnt/nfs", 0, 0);
+        nfs.statfs = None;
+        let mnts = [nfs, mount("/var", 100, 5)];
+        let (state, output) = check_mounts(&mnts, &args("80%", "90%"), &FORMAT);
+        assert_eq!(state, State::Critical);
+        assert!(output.starts_with("DISK CRITICAL - /var 95.0% used (5.0K free) | "));
+
+        let (state, output) = check_mounts(&mnts[..1], &args("80%", "90%"), &FORMAT);
+        assert_eq!(state, State::Unknown);
+        assert!(output.starts_with("DISK UNKNOWN - /mnt/nfs no data | "));
+    }
+
+    #[test]
+    fn check_skips_mounts_without_capacity() {
+        let proc = mount("/proc", 0, 0);
+        let mut btrfs = mount("/home", 100, 50);
+        btrfs.statfs.as_mut().unwrap().files = 0;
+        btrfs.statfs.as_mut().unwrap().files_free = 0;
+        let mut args = args("80%", "90%");
+        args.inode_warning = Some(Threshold::MinFree(10));
+        args.inode_critical = Some(Threshold::MinFree(5));
+        let (state, output) = check_mounts(&[proc.clone(), btrfs], &args, &FORMAT);
+        assert_eq!(state, State::Ok);
+        assert_eq!(
+            output,
+            "DISK OK - 1 file system OK | '/home'=50%;80;90;0;100"
+        );
+
+        let (state, output) = check_mounts(&[proc], &args, &FORMAT);
+        assert_eq!(state, State::Unknown);
+        assert_eq!(
+            output,
+            "DISK UNKNOWN - No file systems with a capacity selected"
+        );
+    }
+
+    #[test]
+    fn check_number_format() {
+        let mnts = [mount("/var", 100, 5)];
+        let format = NumberFormat::new(Scale::Base10);
+        let (_, output) = check_mounts(&mnts, &args("80%", "90%"), &format);
+        assert!(output.starts_with("DISK CRITICAL - /var 95.0% used (5.1k free) | "));
+        let format = NumberFormat::new(Scale::Blocks(1024));
+        let (_, output) = check_mounts(&mnts, &args("80%", "90%"), &format);
+        assert!(output.starts_with("DISK CRITICAL - /var 95.0% used (5 free) | "));
+    }
+
+    #[test]
+    fn perfdata_label_quote() {
+        let (_, output) =
+            check_mounts(&[mount("/mnt/it's", 100, 50)], &args("80%", "90%"), &FORMAT);
+        assert!(output.ends_with("'/mnt/it''s'=50%;80;90;0;100"));
+    }
+}
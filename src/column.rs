@@ -0,0 +1,117 @@
+#![allow(clippy::use_self)]
+
+use crate::mount::Mount;
+
+use clap::ValueEnum;
+use std::cmp::Ordering;
+use strum_macros::{Display, EnumString, VariantNames};
+
+#[derive(Debug, Clone, PartialEq, Eq, Display, ValueEnum, EnumString, VariantNames)]
+#[strum(serialize_all = "snake_case")]
+#[clap(rename_all = "snake_case")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ColumnType {
+    #[clap(alias = "fsname")]
+    #[strum(serialize = "filesystem", serialize = "fsname")]
+    Filesystem,
+    Type,
+    Bar,
+    Used,
+    UsedPercentage,
+    Available,
+    AvailablePercentage,
+    Reserved,
+    Free,
+    Capacity,
+    MountedOn,
+    Delta,
+    Rate,
+    TimeToFull,
+    Host,
+    Status,
+    Inodes,
+    InodesUsed,
+    InodesFree,
+    InodesUsedPercentage,
+    InodeBar,
+}
+
+impl ColumnType {
+    pub const fn label(&self, inodes_mode: bool) -> &str {
+        match self {
+            Self::Filesystem => "Filesystem",
+            Self::Type => "Type",
+            Self::Bar => "",
+            Self::Used => "Used",
+            Self::UsedPercentage => "Used%",
+            Self::Available => "Avail",
+            Self::AvailablePercentage => "Avail%",
+            Self::Reserved => "Reserved",
+            Self::Free => "Free",
+            Self::Capacity => {
+                if inodes_mode {
+                    "Inodes"
+                } else {
+                    "Size"
+                }
+            }
+            Self::MountedOn => "Mounted on",
+            Self::Delta => "Change",
+            Self::Rate => "Rate",
+            Self::TimeToFull => "Full in",
+            Self::Host => "Host",
+            Self::Status => "Status",
+            Self::Inodes => "Inodes",
+            Self::InodesUsed => "IUsed",
+            Self::InodesFree => "IFree",
+            Self::InodesUsedPercentage => "IUse%",
+            Self::InodeBar => "",
+        }
+    }
+
+    /// Compare two mounts by the raw value shown in this column
+    pub fn cmp_mounts(&self, a: &Mount, b: &Mount) -> Ordering {
+        match self {
+            Self::Filesystem => a.mnt_fsname.cmp(&b.mnt_fsname),
+            Self::Type => a.mnt_type.cmp(&b.mnt_type),
+            Self::Bar | Self::UsedPercentage => {
+                cmp_partial(a.used_percentage(), b.used_percentage())
+            }
+            Self::Used => a.used.cmp(&b.used),
+            Self::Available => a.available.cmp(&b.available),
+            Self::AvailablePercentage => {
+                cmp_partial(a.available_percentage(), b.available_percentage())
+            }
+            Self::Reserved => a.reserved.cmp(&b.reserved),
+            Self::Free => a.free().cmp(&b.free()),
+            Self::Capacity => a.capacity.cmp(&b.capacity),
+            Self::MountedOn => a.mnt_dir.cmp(&b.mnt_dir),
+            Self::Delta => {
+                let delta = |mnt: &Mount| mnt.trend.map(|trend| trend.delta);
+                delta(a).cmp(&delta(b))
+            }
+            Self::Rate => {
+                let rate = |mnt: &Mount| mnt.trend.map(|trend| trend.rate);
+                cmp_partial(rate(a), rate(b))
+            }
+            Self::TimeToFull => {
+                let time = |mnt: &Mount| mnt.trend.and_then(|trend| trend.time_to_full);
+                time(a).cmp(&time(b))
+            }
+            Self::Host => a.host.cmp(&b.host),
+            Self::Status => a.status.name().cmp(b.status.name()),
+            Self::Inodes => a.inodes().cmp(&b.inodes()),
+            Self::InodesUsed => a.inodes_used().cmp(&b.inodes_used()),
+            Self::InodesFree => a.inodes_free().cmp(&b.inodes_free()),
+            Self::InodesUsedPercentage | Self::InodeBar => {
+                cmp_partial(a.inodes_used_percentage(), b.inodes_used_percentage())
+            }
+        }
+    }
+}
+
+/// Order missing values first and the rest by their value
+fn cmp_partial<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
@@ -0,0 +1,207 @@
+use dfrs::column::ColumnType;
+use dfrs::diff::{DiffStatus, MountDiff};
+use dfrs::theme::Theme;
+use dfrs::util::{format_percentage, format_signed, NumberFormat, Scale};
+use dfrs::Mount;
+
+use colored::*;
+use std::io::{self, Write};
+
+/// Row color by the direction and size of the usage change: growth of at
+/// least `significant` percentage points in the high usage color, smaller
+/// growth in the medium and shrinking usage in the low usage color.
+fn diff_color(diff: &MountDiff, theme: &Theme, significant: f32) -> Option<Color> {
+    match diff.status() {
+        DiffStatus::Unchanged => None,
+        DiffStatus::Added | DiffStatus::Removed => theme.color_usage_void,
+        DiffStatus::Changed => {
+            let points = diff.used_percentage_delta().unwrap_or_default();
+            if diff.used_delta() <= 0 {
+                theme.color_usage_low
+            } else if points >= significant {
+                theme.color_usage_high
+            } else {
+                theme.color_usage_medium
+            }
+        }
+    }
+}
+
+const fn marker(status: DiffStatus) -> &'static str {
+    match status {
+        DiffStatus::Added => "+",
+        DiffStatus::Removed => "-",
+        DiffStatus::Changed => "~",
+        DiffStatus::Unchanged => " ",
+    }
+}
+
+/// Print the per mount changes between two states as a table
+pub fn display_diffs<W: Write + ?Sized>(
+    w: &mut W,
+    diffs: &[MountDiff],
+    theme: &Theme,
+    delimiter: &NumberFormat,
+    inodes_mode: bool,
+    no_aliases: bool,
+    significant: f32,
+) -> io::Result<()> {
+    let color_heading = theme.color_heading.unwrap_or(Color::White);
+    let fsname_func = if no_aliases {
+        Mount::fsname
+    } else {
+        Mount::fsname_aliased
+    };
+    let signed = |delta: i64| format_signed(delta as f64, delimiter);
+
+    let mut headings = vec![
+        "",
+        ColumnType::Filesystem.label(inodes_mode),
+        ColumnType::UsedPercentage.label(inodes_mode),
+        ColumnType::Delta.label(inodes_mode),
+        ColumnType::Used.label(inodes_mode),
+        ColumnType::Available.label(inodes_mode),
+        ColumnType::Capacity.label(inodes_mode),
+    ];
+    if !inodes_mode {
+        headings.push("Inodes");
+    }
+    headings.push(ColumnType::MountedOn.label(inodes_mode));
+
+    let rows = diffs
+        .iter()
+        .map(|diff| {
+            let mnt = diff.mount();
+            let mut row = vec![
+                marker(diff.status()).to_string(),
+                fsname_func(mnt),
+                format_percentage(mnt.used_percentage()),
+                diff.used_percentage_delta()
+                    .map_or_else(|| "-".to_string(), |points| format!("{:+.1}", points)),
+                signed(diff.used_delta()),
+                signed(diff.free_delta()),
+                signed(diff.capacity_delta()),
+            ];
+            if !inodes_mode {
+                row.push(format_signed(
+                    diff.inodes_used_delta() as f64,
+                    &NumberFormat::new(Scale::Raw),
+                ));
+            }
+            row.push(mnt.mnt_dir.clone());
+            row
+        })
+        .collect::<Vec<_>>();
+
+    let widths = headings
+        .iter()
+        .enumerate()
+        .map(|(idx, heading)| {
+            rows.iter()
+                .map(|row| row[idx].chars().count())
+                .chain(std::iter::once(heading.len()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    let left_aligned = |idx: usize| idx <= 1 || idx == headings.len() - 1;
+    let format_line = |cells: &[&str]| -> String {
+        let line = cells
+            .iter()
+            .enumerate()
+            .map(|(idx, cell)| {
+                if left_aligned(idx) {
+                    format!("{:<width$}", cell, width = widths[idx])
+                } else {
+                    format!("{:>width$}", cell, width = widths[idx])
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        line.trim_end().to_string()
+    };
+
+    writeln!(w, "{}", format_line(&headings).color(color_heading))?;
+    for (diff, row) in diffs.iter().zip(&rows) {
+        let line = format_line(&row.iter().map(String::as_str).collect::<Vec<_>>());
+        match diff_color(diff, theme, significant) {
+            Some(color) => writeln!(w, "{}", line.color(color))?,
+            None => writeln!(w, "{}", line)?,
+        }
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfrs::diff::diff_mounts;
+    use dfrs::mount::StatfsInfo;
+
+    fn mount(dir: &str, blocks_available: u64) -> Mount {
+        let mut mnt = Mount::named("/dev/sda1".into());
+        mnt.mnt_dir = dir.to_string();
+        mnt.statfs = Some(StatfsInfo {
+            block_size: 1,
+            blocks: 1000,
+            blocks_free: blocks_available,
+            blocks_available,
+            files: 100,
+            files_free: 50,
+        });
+        mnt.compute_usage(false);
+        mnt
+    }
+
+    #[test]
+    fn diff_colors() {
+        let theme = Theme::new();
+        let old = [mount("/", 500), mount("/srv", 500), mount("/var", 500)];
+        let new = [
+            mount("/", 400),
+            mount("/srv", 480),
+            mount("/var", 600),
+            mount("/tmp", 500),
+        ];
+        let colors = diff_mounts(&old, &new)
+            .iter()
+            .map(|diff| diff_color(diff, &theme, 5.0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            colors,
+            [
+                theme.color_usage_high,
+                theme.color_usage_medium,
+                theme.color_usage_void,
+                theme.color_usage_low
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_table() {
+        colored::control::set_override(false);
+        let diffs = diff_mounts(&[mount("/", 500)], &[mount("/", 400), mount("/srv", 500)]);
+        let mut out = Vec::new();
+        display_diffs(
+            &mut out,
+            &diffs,
+            &Theme::new(),
+            &NumberFormat::new(Scale::Raw),
+            false,
+            false,
+            5.0,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "  Filesystem  Used% Change Used Avail  Size Inodes Mounted on",
+                "~ /dev/sda1   60.0%  +10.0 +100  -100     0      0 /",
+                "+ /dev/sda1   50.0%      - +500  +500 +1000    +50 /srv",
+            ]
+        );
+    }
+}
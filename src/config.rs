@@ -0,0 +1,646 @@
+//! Settings read from `config.toml` files.
+//!
+//! The files are layered, later ones overriding single values of earlier
+//! ones: the built-in defaults, `/etc/dfrs/config.toml` and
+//! `$XDG_CONFIG_HOME/dfrs/config.toml`. A profile selected by name is
+//! applied from `[profiles.<name>]` of every file on top of that.
+//!
+//! ```toml
+//! columns = ["filesystem", "bar", "used_percentage", "mounted_on"]
+//!
+//! [theme]
+//! bar_width = 30
+//! color_usage_high = "bright red"
+//!
+//! [filters]
+//! exclude_types = ["squashfs"]
+//!
+//! [profiles.ops.filters]
+//! where = "used_percentage > 80"
+//! ```
+
+use crate::column::ColumnType;
+use crate::errors::{Error, Result};
+use crate::expr::Expr;
+use crate::filter::{DisplayFilter, MountFilter};
+use crate::theme::Theme;
+
+use colored::Color;
+use glob::Pattern;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml::{Table, Value};
+
+pub const SYSTEM_PATH: &str = "/etc/dfrs/config.toml";
+
+/// Sections grouping keys, besides `profiles`
+const SECTIONS: [&str; 2] = ["theme", "filters"];
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Char,
+    Str,
+    Percentage,
+    Width,
+    Color,
+    Bool,
+    Columns,
+    List,
+    Globs,
+    Display,
+    Expr,
+}
+
+const KEYS: [(&str, Kind); 24] = [
+    ("theme.char_bar_filled", Kind::Char),
+    ("theme.char_bar_empty", Kind::Char),
+    ("theme.char_bar_reserved", Kind::Char),
+    ("theme.char_bar_open", Kind::Str),
+    ("theme.char_bar_close", Kind::Str),
+    ("theme.threshold_usage_medium", Kind::Percentage),
+    ("theme.threshold_usage_high", Kind::Percentage),
+    ("theme.color_heading", Kind::Color),
+    ("theme.color_usage_low", Kind::Color),
+    ("theme.color_usage_medium", Kind::Color),
+    ("theme.color_usage_high", Kind::Color),
+    ("theme.color_usage_void", Kind::Color),
+    ("theme.bar_width", Kind::Width),
+    ("theme.color_by_worse", Kind::Bool),
+    ("columns", Kind::Columns),
+    ("filters.display", Kind::Display),
+    ("filters.local", Kind::Bool),
+    ("filters.types", Kind::List),
+    ("filters.exclude_types", Kind::List),
+    ("filters.include_mounts", Kind::Globs),
+    ("filters.exclude_mounts", Kind::Globs),
+    ("filters.devices", Kind::Globs),
+    ("filters.where", Kind::Expr),
+    ("filters.dedupe", Kind::Bool),
+];
+
+/// Where the effective value of a setting comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Profile { name: String, path: PathBuf },
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => f.write_str("default"),
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Profile { name, path } => write!(f, "{} (profile {})", path.display(), name),
+        }
+    }
+}
+
+/// The merged settings of all configuration files.
+#[derive(Debug, Clone)]
+pub struct Config {
+    values: BTreeMap<&'static str, (Value, Source)>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Config {
+    /// The built-in defaults
+    pub fn new() -> Self {
+        let theme = Theme::new();
+        let string = |s: &str| Value::String(s.to_string());
+        let list = |items: &[String]| Value::Array(items.iter().map(|s| string(s)).collect());
+        let color = |color: Option<Color>| string(&color_name(color));
+        let defaults = [
+            (
+                "theme.char_bar_filled",
+                string(&theme.char_bar_filled.to_string()),
+            ),
+            (
+                "theme.char_bar_empty",
+                string(&theme.char_bar_empty.to_string()),
+            ),
+            (
+                "theme.char_bar_reserved",
+                string(&theme.char_bar_reserved.to_string()),
+            ),
+            ("theme.char_bar_open", string(&theme.char_bar_open)),
+            ("theme.char_bar_close", string(&theme.char_bar_close)),
+            (
+                "theme.threshold_usage_medium",
+                Value::Float(theme.threshold_usage_medium.into()),
+            ),
+            (
+                "theme.threshold_usage_high",
+                Value::Float(theme.threshold_usage_high.into()),
+            ),
+            ("theme.color_heading", color(theme.color_heading)),
+            ("theme.color_usage_low", color(theme.color_usage_low)),
+            ("theme.color_usage_medium", color(theme.color_usage_medium)),
+            ("theme.color_usage_high", color(theme.color_usage_high)),
+            ("theme.color_usage_void", color(theme.color_usage_void)),
+            ("theme.bar_width", Value::Integer(theme.bar_width as i64)),
+            ("theme.color_by_worse", Value::Boolean(theme.color_by_worse)),
+            (
+                "columns",
+                list(
+                    &theme
+                        .columns
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>(),
+                ),
+            ),
+            (
+                "filters.display",
+                string(&DisplayFilter::Minimal.to_string()),
+            ),
+            ("filters.local", Value::Boolean(false)),
+            ("filters.types", list(&[])),
+            ("filters.exclude_types", list(&[])),
+            ("filters.include_mounts", list(&[])),
+            ("filters.exclude_mounts", list(&[])),
+            ("filters.devices", list(&[])),
+            ("filters.dedupe", Value::Boolean(false)),
+        ];
+        Self {
+            values: defaults
+                .into_iter()
+                .map(|(key, value)| (key, (value, Source::Default)))
+                .collect(),
+        }
+    }
+
+    /// `$XDG_CONFIG_HOME/dfrs/config.toml`, falling back to `~/.config`
+    pub fn user_path() -> Option<PathBuf> {
+        let absolute = |var: &str| {
+            env::var_os(var)
+                .map(PathBuf::from)
+                .filter(|path| path.is_absolute())
+        };
+        absolute("XDG_CONFIG_HOME")
+            .or_else(|| absolute("HOME").map(|home| home.join(".config")))
+            .map(|config| config.join("dfrs").join("config.toml"))
+    }
+
+    /// The system and the user configuration file, in the order they apply
+    pub fn paths() -> Vec<PathBuf> {
+        std::iter::once(PathBuf::from(SYSTEM_PATH))
+            .chain(Self::user_path())
+            .collect()
+    }
+
+    /// Read the system and user configuration, each being optional
+    pub fn load(profile: Option<&str>) -> Result<Self> {
+        Self::load_paths(&Self::paths(), profile)
+    }
+
+    /// Layer the files over the defaults, skipping missing ones, and apply
+    /// the profile of every file on top
+    pub fn load_paths<P: AsRef<Path>>(paths: &[P], profile: Option<&str>) -> Result<Self> {
+        let mut tables = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            let content = match fs::read_to_string(path) {
+                Ok(content) => content,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(source) => {
+                    return Err(Error::ReadConfig {
+                        path: path.to_path_buf(),
+                        source,
+                    })
+                }
+            };
+            let table = content
+                .parse::<Table>()
+                .map_err(|source| Error::InvalidConfig {
+                    path: path.to_path_buf(),
+                    source,
+                })?;
+            tables.push((path.to_path_buf(), table));
+        }
+
+        let mut config = Self::new();
+        for (path, table) in &tables {
+            config.apply(table, "", &Source::File(path.clone()), path)?;
+        }
+        if let Some(name) = profile {
+            let mut found = false;
+            for (path, table) in &tables {
+                let Some(value) = table.get("profiles").and_then(|p| p.get(name)) else {
+                    continue;
+                };
+                let invalid = |message: &str| Error::InvalidConfigValue {
+                    path: path.clone(),
+                    key: format!("profiles.{}", name),
+                    message: message.to_string(),
+                };
+                let profile = value
+                    .as_table()
+                    .ok_or_else(|| invalid("expected a table"))?;
+                let source = Source::Profile {
+                    name: name.to_string(),
+                    path: path.clone(),
+                };
+                config.apply(profile, "", &source, path)?;
+                found = true;
+            }
+            if !found {
+                return Err(Error::UnknownProfile(name.to_string()));
+            }
+        }
+        Ok(config)
+    }
+
+    fn apply(&mut self, table: &Table, prefix: &str, source: &Source, path: &Path) -> Result<()> {
+        for (key, value) in table {
+            let name = format!("{}{}", prefix, key);
+            let invalid = |message: String| Error::InvalidConfigValue {
+                path: path.to_path_buf(),
+                key: name.clone(),
+                message,
+            };
+            if name == "profiles" && matches!(source, Source::File(_)) {
+                if !value.is_table() {
+                    return Err(invalid("expected a table".to_string()));
+                }
+                continue;
+            }
+            if prefix.is_empty() && SECTIONS.contains(&key.as_str()) {
+                let section = value
+                    .as_table()
+                    .ok_or_else(|| invalid("expected a table".to_string()))?;
+                self.apply(section, &format!("{}.", key), source, path)?;
+                continue;
+            }
+            let (key, kind) = KEYS
+                .iter()
+                .find(|(known, _)| *known == name)
+                .ok_or_else(|| invalid("unknown key".to_string()))?;
+            check(*kind, value).map_err(invalid)?;
+            self.values.insert(key, (value.clone(), source.clone()));
+        }
+        Ok(())
+    }
+
+    /// Every set value with where it comes from, ordered by key
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Value, &Source)> {
+        self.values
+            .iter()
+            .map(|(key, (value, source))| (*key, value, source))
+    }
+
+    /// Whether a configuration file sets the value rather than the defaults
+    pub fn is_set(&self, key: &str) -> bool {
+        self.values
+            .get(key)
+            .is_some_and(|(_, source)| *source != Source::Default)
+    }
+
+    fn str(&self, key: &str) -> Option<&str> {
+        self.values.get(key).and_then(|(value, _)| value.as_str())
+    }
+
+    fn strings(&self, key: &str) -> Vec<String> {
+        self.values
+            .get(key)
+            .and_then(|(value, _)| value.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| item.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn number(&self, key: &str) -> Option<f64> {
+        self.values.get(key).and_then(|(value, _)| number(value))
+    }
+
+    fn bool(&self, key: &str) -> bool {
+        self.values
+            .get(key)
+            .and_then(|(value, _)| value.as_bool())
+            .unwrap_or_default()
+    }
+
+    fn globs(&self, key: &str) -> Vec<Pattern> {
+        self.strings(key)
+            .iter()
+            .filter_map(|glob| Pattern::new(glob).ok())
+            .collect()
+    }
+
+    /// The theme with the configured columns
+    pub fn theme(&self) -> Theme {
+        let mut theme = Theme::new();
+        let char = |key| self.str(key).and_then(|s| s.chars().next());
+        let color = |key| self.str(key).and_then(|s| parse_color(s).ok());
+        if let Some(c) = char("theme.char_bar_filled") {
+            theme.char_bar_filled = c;
+        }
+        if let Some(c) = char("theme.char_bar_empty") {
+            theme.char_bar_empty = c;
+        }
+        if let Some(c) = char("theme.char_bar_reserved") {
+            theme.char_bar_reserved = c;
+        }
+        if let Some(s) = self.str("theme.char_bar_open") {
+            theme.char_bar_open = s.to_string();
+        }
+        if let Some(s) = self.str("theme.char_bar_close") {
+            theme.char_bar_close = s.to_string();
+        }
+        if let Some(n) = self.number("theme.threshold_usage_medium") {
+            theme.threshold_usage_medium = n as f32;
+        }
+        if let Some(n) = self.number("theme.threshold_usage_high") {
+            theme.threshold_usage_high = n as f32;
+        }
+        if let Some(c) = color("theme.color_heading") {
+            theme.color_heading = c;
+        }
+        if let Some(c) = color("theme.color_usage_low") {
+            theme.color_usage_low = c;
+        }
+        if let Some(c) = color("theme.color_usage_medium") {
+            theme.color_usage_medium = c;
+        }
+        if let Some(c) = color("theme.color_usage_high") {
+            theme.color_usage_high = c;
+        }
+        if let Some(c) = color("theme.color_usage_void") {
+            theme.color_usage_void = c;
+        }
+        if let Some(n) = self.number("theme.bar_width") {
+            theme.bar_width = n as usize;
+        }
+        theme.color_by_worse = self.bool("theme.color_by_worse");
+        theme.columns = self
+            .strings("columns")
+            .iter()
+            .filter_map(|column| ColumnType::from_str(column).ok())
+            .collect();
+        theme
+    }
+
+    pub fn display_filter(&self) -> DisplayFilter {
+        self.str("filters.display")
+            .and_then(|level| DisplayFilter::from_str(level).ok())
+            .unwrap_or(DisplayFilter::Minimal)
+    }
+
+    pub fn local_only(&self) -> bool {
+        self.bool("filters.local")
+    }
+
+    pub fn dedupe(&self) -> bool {
+        self.bool("filters.dedupe")
+    }
+
+    pub fn mount_filter(&self) -> MountFilter {
+        MountFilter {
+            types: self.strings("filters.types"),
+            exclude_types: self.strings("filters.exclude_types"),
+            include_mounts: self.globs("filters.include_mounts"),
+            exclude_mounts: self.globs("filters.exclude_mounts"),
+            devices: self.globs("filters.devices"),
+        }
+    }
+
+    pub fn predicate(&self) -> Option<Expr> {
+        self.str("filters.where").and_then(|expr| expr.parse().ok())
+    }
+}
+
+const fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(n) => Some(*n as f64),
+        Value::Float(n) => Some(*n),
+        _ => None,
+    }
+}
+
+/// Whether a value fits the kind of its key, explaining why not
+fn check(kind: Kind, value: &Value) -> std::result::Result<(), String> {
+    let str = || {
+        value
+            .as_str()
+            .ok_or_else(|| format!("expected a string, found {}", value.type_str()))
+    };
+    let strings = || {
+        value
+            .as_array()
+            .and_then(|items| items.iter().map(Value::as_str).collect::<Option<Vec<_>>>())
+            .ok_or_else(|| "expected a list of strings".to_string())
+    };
+    match kind {
+        Kind::Char => match str()?.chars().count() {
+            1 => Ok(()),
+            _ => Err("expected a single character".to_string()),
+        },
+        Kind::Str => str().map(|_| ()),
+        Kind::Percentage => match number(value) {
+            Some(n) if (0.0..=100.0).contains(&n) => Ok(()),
+            _ => Err("expected a percentage between 0 and 100".to_string()),
+        },
+        Kind::Width => match value.as_integer() {
+            Some(n) if n >= 0 => Ok(()),
+            _ => Err("expected a non-negative integer".to_string()),
+        },
+        Kind::Color => parse_color(str()?).map(|_| ()),
+        Kind::Bool => match value.as_bool() {
+            Some(_) => Ok(()),
+            None => Err(format!("expected a boolean, found {}", value.type_str())),
+        },
+        Kind::Columns => strings()?.into_iter().try_for_each(|column| {
+            ColumnType::from_str(column)
+                .map(|_| ())
+                .map_err(|_| format!("unknown column {}", column))
+        }),
+        Kind::List => strings().map(|_| ()),
+        Kind::Globs => strings()?.into_iter().try_for_each(|glob| {
+            Pattern::new(glob)
+                .map(|_| ())
+                .map_err(|err| format!("invalid glob {}: {}", glob, err))
+        }),
+        Kind::Display => DisplayFilter::from_str(str()?)
+            .map(|_| ())
+            .map_err(|_| "expected one of minimal, more or all".to_string()),
+        Kind::Expr => str()?
+            .parse::<Expr>()
+            .map(|_| ())
+            .map_err(|err| err.to_string()),
+    }
+}
+
+/// Parse a color name as understood by `colored`, `#rrggbb` or `none`
+fn parse_color(s: &str) -> std::result::Result<Option<Color>, String> {
+    if s == "none" {
+        return Ok(None);
+    }
+    if let Some(hex) = s.strip_prefix('#').filter(|hex| hex.len() == 6) {
+        let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16);
+        if let (Ok(r), Ok(g), Ok(b)) = (channel(0), channel(2), channel(4)) {
+            return Ok(Some(Color::TrueColor { r, g, b }));
+        }
+    }
+    Color::from_str(s)
+        .map(Some)
+        .map_err(|_| format!("unknown color {}", s))
+}
+
+fn color_name(color: Option<Color>) -> String {
+    let Some(color) = color else {
+        return "none".to_string();
+    };
+    match color {
+        Color::Black => "black",
+        Color::Red => "red",
+        Color::Green => "green",
+        Color::Yellow => "yellow",
+        Color::Blue => "blue",
+        Color::Magenta => "magenta",
+        Color::Cyan => "cyan",
+        Color::White => "white",
+        Color::BrightBlack => "bright black",
+        Color::BrightRed => "bright red",
+        Color::BrightGreen => "bright green",
+        Color::BrightYellow => "bright yellow",
+        Color::BrightBlue => "bright blue",
+        Color::BrightMagenta => "bright magenta",
+        Color::BrightCyan => "bright cyan",
+        Color::BrightWhite => "bright white",
+        Color::TrueColor { r, g, b } => return format!("#{:02x}{:02x}{:02x}", r, g, b),
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(name: &str, content: &str) -> PathBuf {
+        let path =
+            env::temp_dir().join(format!("dfrs-config-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn config_defaults_match_theme() {
+        let theme = Config::new().theme();
+        let default = Theme::new();
+        assert_eq!(theme.columns, default.columns);
+        assert_eq!(theme.char_bar_filled, default.char_bar_filled);
+        assert_eq!(theme.color_heading, default.color_heading);
+        assert_eq!(theme.bar_width, default.bar_width);
+    }
+
+    #[test]
+    fn config_layers_and_profiles() {
+        let system = write(
+            "system",
+            "columns = [\"fsname\", \"mounted_on\"]\n\
+             [theme]\n\
+             bar_width = 30\n\
+             color_usage_high = \"bright red\"\n\
+             [profiles.ops.filters]\n\
+             where = \"used_percentage > 80\"\n\
+             types = [\"ext4\"]\n",
+        );
+        let user = write(
+            "user",
+            "[theme]\n\
+             bar_width = 10\n\
+             color_heading = \"none\"\n\
+             [filters]\n\
+             exclude_mounts = [\"/var/lib/docker/**\"]\n\
+             [profiles.ops.theme]\n\
+             color_usage_void = \"#102030\"\n",
+        );
+        let missing = env::temp_dir().join("dfrs-config-missing.toml");
+
+        let config = Config::load_paths(&[&system, &user, &missing], None).unwrap();
+        let theme = config.theme();
+        assert_eq!(theme.bar_width, 10);
+        assert_eq!(theme.color_heading, None);
+        assert_eq!(theme.color_usage_high, Some(Color::BrightRed));
+        assert_eq!(
+            theme.columns,
+            [ColumnType::Filesystem, ColumnType::MountedOn]
+        );
+        assert_eq!(config.mount_filter().exclude_mounts.len(), 1);
+        assert!(config.predicate().is_none());
+        let sources = config
+            .entries()
+            .map(|(key, _, source)| (key, source.clone()))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(sources["theme.bar_width"], Source::File(user.clone()));
+        assert_eq!(sources["columns"], Source::File(system.clone()));
+        assert_eq!(sources["theme.char_bar_open"], Source::Default);
+        assert!(config.is_set("columns"));
+        assert!(!config.is_set("theme.char_bar_open"));
+        assert!(!Config::new().is_set("columns"));
+
+        let config = Config::load_paths(&[&system, &user], Some("ops")).unwrap();
+        assert!(config.predicate().is_some());
+        assert_eq!(config.mount_filter().types, ["ext4"]);
+        assert_eq!(
+            config.theme().color_usage_void,
+            Some(Color::TrueColor {
+                r: 0x10,
+                g: 0x20,
+                b: 0x30
+            })
+        );
+        let source = config
+            .entries()
+            .find(|(key, _, _)| *key == "filters.where")
+            .map(|(_, _, source)| source.to_string())
+            .unwrap();
+        assert_eq!(source, format!("{} (profile ops)", system.display()));
+
+        let err = Config::load_paths(&[&system], Some("dev")).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown profile dev");
+        fs::remove_file(&system).unwrap();
+        fs::remove_file(&user).unwrap();
+    }
+
+    #[test]
+    fn config_invalid_values() {
+        let error = |content: &str| {
+            let path = write("invalid", content);
+            let err = Config::load_paths(&[&path], None).unwrap_err().to_string();
+            fs::remove_file(&path).unwrap();
+            err.split_once(": ").unwrap().1.to_string()
+        };
+        assert_eq!(
+            error("[theme]\nbar_width = -1"),
+            "theme.bar_width: expected a non-negative integer"
+        );
+        assert_eq!(
+            error("[theme]\ncolor_heading = \"mauve\""),
+            "theme.color_heading: unknown color mauve"
+        );
+        assert_eq!(
+            error("columns = [\"capacity\", \"nope\"]"),
+            "columns: unknown column nope"
+        );
+        assert_eq!(
+            error("[filters]\nlocal = \"yes\""),
+            "filters.local: expected a boolean, found string"
+        );
+        assert_eq!(error("bar_width = 3"), "bar_width: unknown key");
+    }
+}
@@ -0,0 +1,862 @@
+//! GNU df compatible command line and output.
+//!
+//! Used when the binary is invoked as `df`, e.g., through a symlink, or
+//! with `--df-compat`. The GNU options are mapped onto [`Args`], and the
+//! mounts are selected and printed the way GNU df does, so scripts parsing
+//! `df -P` keep working.
+
+use crate::args::Args;
+use crate::output::plain_value;
+use crate::{locale_thousands_separator, mount_query, number_format};
+
+use dfrs::column::ColumnType;
+use dfrs::filter::MountFilter;
+use dfrs::util::{self, format_rounded_up, NumberFormat};
+use dfrs::Mount;
+
+use anyhow::{bail, Result};
+use clap::{ArgAction, Parser};
+use nix::errno::Errno;
+use nix::sys::stat;
+use std::ffi::{CStr, OsString};
+use std::io::{self, stdout, BufWriter, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+
+/// File system types GNU df hides unless `--all` is given
+const DUMMY_TYPES: [&str; 14] = [
+    "autofs",
+    "proc",
+    "subfs",
+    "debugfs",
+    "devpts",
+    "fusectl",
+    "fuse.portal",
+    "mqueue",
+    "rpc_pipefs",
+    "sysfs",
+    "devfs",
+    "kernfs",
+    "ignore",
+    "none",
+];
+
+#[derive(Debug, Parser)]
+#[command(name = "df", version, about = "Show file system usage like GNU df", long_about = None)]
+#[command(disable_help_flag = true)]
+pub struct DfArgs {
+    /// Include pseudo, duplicate and inaccessible file systems
+    #[arg(short, long)]
+    all: bool,
+    /// Scale sizes by SIZE before printing them (e.g., -BM prints sizes in units of 1,048,576 bytes)
+    #[arg(short = 'B', long, value_name = "SIZE", value_parser = parse_spec, overrides_with_all = ["human_readable", "si", "kilo"])]
+    block_size: Option<Spec>,
+    /// Print sizes in powers of 1024 (e.g., 1023M)
+    #[arg(short = 'h', long, overrides_with_all = ["block_size", "si", "kilo"])]
+    human_readable: bool,
+    /// Print sizes in powers of 1000 (e.g., 1.1G)
+    #[arg(short = 'H', long, overrides_with_all = ["block_size", "human_readable", "kilo"])]
+    si: bool,
+    /// List inode information instead of block usage
+    #[arg(short, long, conflicts_with = "output")]
+    inodes: bool,
+    /// Like --block-size=1K
+    #[arg(short = 'k', overrides_with_all = ["block_size", "human_readable", "si"])]
+    kilo: bool,
+    /// Limit listing to local file systems
+    #[arg(short, long)]
+    local: bool,
+    /// Do not invoke sync before getting usage info (default)
+    #[arg(long, overrides_with = "sync")]
+    no_sync: bool,
+    /// Use the output format defined by FIELD_LIST, or print all fields if FIELD_LIST is omitted
+    #[arg(long, value_name = "FIELD_LIST", num_args = 0..=1, require_equals = true, default_missing_value = "")]
+    output: Vec<String>,
+    /// Use the POSIX output format
+    #[arg(short = 'P', long, conflicts_with = "output")]
+    portability: bool,
+    /// Invoke sync before getting usage info
+    #[arg(long, overrides_with = "no_sync")]
+    sync: bool,
+    /// Produce a grand total
+    #[arg(long)]
+    total: bool,
+    /// Limit listing to file systems of type TYPE
+    #[arg(short = 't', long = "type", value_name = "TYPE")]
+    types: Vec<String>,
+    /// Print file system type
+    #[arg(short = 'T', long, conflicts_with = "output")]
+    print_type: bool,
+    /// Limit listing to file systems not of type TYPE
+    #[arg(short = 'x', long = "exclude-type", value_name = "TYPE")]
+    exclude_types: Vec<String>,
+    /// Ignored
+    #[arg(short = 'v', hide = true, action = ArgAction::Count)]
+    ignored: u8,
+    /// Switch into this mode when invoked under another name
+    #[arg(long, hide = true)]
+    df_compat: bool,
+    /// Print help
+    #[arg(long, action = ArgAction::Help)]
+    help: Option<bool>,
+    files: Vec<PathBuf>,
+}
+
+/// Unit of printed sizes, from `--block-size` or the environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Spec {
+    /// Powers of 1024, `-h` or `human-readable`
+    Human,
+    /// Powers of 1000, `-H` or `si`
+    Si,
+    /// Blocks of this many bytes, with grouped thousands for a leading `'`
+    /// and the unit after every size when given without a number, like `KB`
+    Bytes {
+        size: u64,
+        grouped: bool,
+        suffixed: bool,
+    },
+}
+
+fn parse_spec(spec: &str) -> Result<Spec, String> {
+    let (grouped, size) = spec
+        .strip_prefix('\'')
+        .map_or((false, spec), |size| (true, size));
+    match size {
+        "human-readable" => Ok(Spec::Human),
+        "si" => Ok(Spec::Si),
+        spec => util::parse_block_size(spec)
+            .map(|size| Spec::Bytes {
+                size,
+                grouped,
+                suffixed: !spec.starts_with(|c: char| c.is_ascii_digit()),
+            })
+            .map_err(|err| err.to_string()),
+    }
+}
+
+/// Layout of the heading, following the options that select the fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Default,
+    Inodes,
+    Human,
+    Posix,
+    Output,
+}
+
+/// Field of `--output`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Source,
+    Fstype,
+    Itotal,
+    Iused,
+    Iavail,
+    Ipcent,
+    Size,
+    Used,
+    Avail,
+    Pcent,
+    File,
+    Target,
+}
+
+impl Field {
+    /// Every field in the order of a bare `--output`
+    const ALL: [Self; 12] = [
+        Self::Source,
+        Self::Fstype,
+        Self::Itotal,
+        Self::Iused,
+        Self::Iavail,
+        Self::Ipcent,
+        Self::Size,
+        Self::Used,
+        Self::Avail,
+        Self::Pcent,
+        Self::File,
+        Self::Target,
+    ];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Source => "source",
+            Self::Fstype => "fstype",
+            Self::Itotal => "itotal",
+            Self::Iused => "iused",
+            Self::Iavail => "iavail",
+            Self::Ipcent => "ipcent",
+            Self::Size => "size",
+            Self::Used => "used",
+            Self::Avail => "avail",
+            Self::Pcent => "pcent",
+            Self::File => "file",
+            Self::Target => "target",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|field| field.name() == name)
+    }
+
+    /// The dfrs column showing the same value, the file argument has none
+    const fn column(self) -> Option<ColumnType> {
+        Some(match self {
+            Self::Source => ColumnType::Filesystem,
+            Self::Fstype => ColumnType::Type,
+            Self::Itotal => ColumnType::Inodes,
+            Self::Iused => ColumnType::InodesUsed,
+            Self::Iavail => ColumnType::InodesFree,
+            Self::Ipcent => ColumnType::InodesUsedPercentage,
+            Self::Size => ColumnType::Capacity,
+            Self::Used => ColumnType::Used,
+            Self::Avail => ColumnType::Available,
+            Self::Pcent => ColumnType::UsedPercentage,
+            Self::Target => ColumnType::MountedOn,
+            Self::File => return None,
+        })
+    }
+
+    const fn min_width(self) -> usize {
+        match self {
+            Self::Source => 14,
+            Self::Fstype | Self::Ipcent | Self::Pcent | Self::File => 4,
+            Self::Target => 0,
+            _ => 5,
+        }
+    }
+
+    const fn right_aligned(self) -> bool {
+        !matches!(
+            self,
+            Self::Source | Self::Fstype | Self::File | Self::Target
+        )
+    }
+
+    fn heading(self, mode: Mode, spec: Spec) -> String {
+        match (self, mode, spec) {
+            (Self::Source, _, _) => "Filesystem",
+            (Self::Fstype, _, _) => "Type",
+            (Self::Itotal, _, _) => "Inodes",
+            (Self::Iused, _, _) => "IUsed",
+            (Self::Iavail, _, _) => "IFree",
+            (Self::Ipcent, _, _) => "IUse%",
+            (Self::Size, Mode::Posix, Spec::Bytes { size, .. }) => {
+                return format!("{}-blocks", size)
+            }
+            (Self::Size, _, Spec::Bytes { size, .. }) => {
+                return format!("{}-blocks", block_label(size))
+            }
+            (Self::Size, _, _) => "Size",
+            (Self::Used, _, _) => "Used",
+            (Self::Avail, Mode::Default | Mode::Posix, _) => "Available",
+            (Self::Avail, _, _) => "Avail",
+            (Self::Pcent, Mode::Posix, _) => "Capacity",
+            (Self::Pcent, _, _) => "Use%",
+            (Self::File, _, _) => "File",
+            (Self::Target, _, _) => "Mounted on",
+        }
+        .to_string()
+    }
+
+    /// The value of the column in the format of GNU df, `-` for the usage
+    /// of inaccessible file systems
+    fn value(self, row: &Row, spec: Spec, format: &NumberFormat) -> String {
+        let mnt = &row.mnt;
+        let percentage = |p: Option<u64>| p.map_or_else(|| "-".to_string(), |p| format!("{}%", p));
+        let value = match self.column() {
+            None => return printable(&row.file),
+            Some(ColumnType::Filesystem) => return printable(&mnt.mnt_fsname),
+            Some(ColumnType::Type) => return printable(&mnt.mnt_type),
+            Some(ColumnType::MountedOn) => return printable(&mnt.mnt_dir),
+            Some(_) if mnt.statfs.is_none() => return "-".to_string(),
+            Some(ColumnType::UsedPercentage) => {
+                return percentage(mnt.used_percentage_rounded_up())
+            }
+            Some(ColumnType::InodesUsedPercentage) => {
+                return percentage(mnt.inodes_used_percentage_rounded_up())
+            }
+            Some(column) => plain_value(mnt, &column, format, true).unwrap_or_default(),
+        };
+        match spec {
+            Spec::Bytes {
+                size,
+                suffixed: true,
+                ..
+            } if matches!(self, Self::Size | Self::Used | Self::Avail) => {
+                value + block_label(size).trim_start_matches('1')
+            }
+            _ => value,
+        }
+    }
+}
+
+/// A line of the table, the usage is unknown for inaccessible file systems
+#[derive(Debug, Clone)]
+struct Row {
+    mnt: Mount,
+    file: String,
+}
+
+impl Row {
+    fn new(mnt: &Mount, file: &str) -> Self {
+        Self {
+            mnt: mnt.clone(),
+            file: file.to_string(),
+        }
+    }
+}
+
+impl DfArgs {
+    /// Unit of printed sizes, the last of `-B`, `-h`, `-H` and `-k` wins
+    /// over `-P` and the `DF_BLOCK_SIZE`, `BLOCK_SIZE` and `BLOCKSIZE`
+    /// environment variables
+    fn spec(&self) -> Spec {
+        let posix_size = if std::env::var_os("POSIXLY_CORRECT").is_some() {
+            512
+        } else {
+            1024
+        };
+        let default = Spec::Bytes {
+            size: posix_size,
+            grouped: false,
+            suffixed: false,
+        };
+        if self.human_readable {
+            Spec::Human
+        } else if self.si {
+            Spec::Si
+        } else if self.kilo {
+            Spec::Bytes {
+                size: 1024,
+                grouped: false,
+                suffixed: false,
+            }
+        } else if let Some(spec) = self.block_size {
+            spec
+        } else if self.portability {
+            default
+        } else {
+            ["DF_BLOCK_SIZE", "BLOCK_SIZE", "BLOCKSIZE"]
+                .iter()
+                .find_map(|var| std::env::var(var).ok())
+                .and_then(|spec| parse_spec(&spec).ok())
+                .unwrap_or(default)
+        }
+    }
+
+    const fn mode(&self, spec: Spec) -> Mode {
+        if !self.output.is_empty() {
+            Mode::Output
+        } else if self.inodes {
+            Mode::Inodes
+        } else if matches!(spec, Spec::Human | Spec::Si) {
+            Mode::Human
+        } else if self.portability {
+            Mode::Posix
+        } else {
+            Mode::Default
+        }
+    }
+
+    fn fields(&self, mode: Mode) -> Result<Vec<Field>> {
+        let usage = if mode == Mode::Inodes {
+            [Field::Itotal, Field::Iused, Field::Iavail, Field::Ipcent]
+        } else {
+            [Field::Size, Field::Used, Field::Avail, Field::Pcent]
+        };
+        if mode != Mode::Output {
+            return Ok(std::iter::once(Field::Source)
+                .chain(self.print_type.then_some(Field::Fstype))
+                .chain(usage)
+                .chain(std::iter::once(Field::Target))
+                .collect());
+        }
+
+        let mut fields = Vec::new();
+        for list in &self.output {
+            if list.is_empty() {
+                fields.extend(Field::ALL);
+                continue;
+            }
+            for name in list.split(',') {
+                let Some(field) = Field::from_name(name) else {
+                    bail!("option --output: field '{}' unknown", name);
+                };
+                if fields.contains(&field) {
+                    bail!("option --output: field '{}' used more than once", name);
+                }
+                fields.push(field);
+            }
+        }
+        Ok(fields)
+    }
+
+    /// The dfrs arguments selecting the same mounts and sizes. Pseudo and
+    /// duplicate file systems are left to GNU's rules.
+    fn dfrs_args(&self, spec: Spec) -> Args {
+        let mut args = Args::parse_from(["dfrs", "--all", "--timeout", "0"]);
+        args.local = self.local;
+        args.types = self.types.clone();
+        args.exclude_types = self.exclude_types.clone();
+        args.total = self.total;
+        match spec {
+            Spec::Human => args.base2 = true,
+            Spec::Si => args.base10 = true,
+            Spec::Bytes { size, grouped, .. } => {
+                args.block_size = Some(size);
+                args.thousands = grouped
+                    .then(locale_thousands_separator)
+                    .flatten()
+                    .map(String::from);
+            }
+        }
+        args
+    }
+
+    /// Rows of every mounted file system, without pseudo and duplicate
+    /// file systems unless `--all` is given
+    fn mount_rows(&self, mnts: Vec<Mount>, failed: &mut bool) -> Vec<Row> {
+        let mnts = if self.all {
+            mnts
+        } else {
+            let real = mnts
+                .into_iter()
+                .filter(|mnt| !DUMMY_TYPES.contains(&mnt.mnt_type.as_str()))
+                .collect();
+            dedupe(real, self.total)
+        };
+
+        let mut rows = Vec::new();
+        for mnt in &mnts {
+            match (mnt.statfs, mnt.status.errno()) {
+                (Some(stat), _) if stat.blocks == 0 && !self.all => {}
+                (Some(_), _) => rows.push(Row::new(mnt, "-")),
+                (None, Some(Errno::EACCES | Errno::ENOENT)) => {
+                    if self.all {
+                        let mut row = Row::new(mnt, "-");
+                        row.mnt.mnt_type = "-".to_string();
+                        rows.push(row);
+                    }
+                }
+                (None, errno) => {
+                    let reason = errno.map_or_else(|| mnt.status.to_string(), strerror);
+                    eprintln!("df: {}: {}", mnt.mnt_dir, reason);
+                    *failed = true;
+                }
+            }
+        }
+        rows
+    }
+
+    /// Rows of the file systems containing the files, in the order of the
+    /// files. Mounted block devices stand for the file system on them.
+    fn file_rows(
+        &self,
+        mnts: &[Mount],
+        selected: impl Fn(&Mount) -> bool,
+        failed: &mut bool,
+    ) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for file in &self.files {
+            let path = match file.canonicalize() {
+                Ok(path) => path,
+                Err(err) => {
+                    eprintln!("df: {}: {}", file.display(), describe(&err));
+                    *failed = true;
+                    continue;
+                }
+            };
+            let device = path
+                .metadata()
+                .is_ok_and(|meta| meta.file_type().is_block_device())
+                .then(|| {
+                    mnts.iter().rev().find(|mnt| {
+                        Path::new(&mnt.mnt_fsname).canonicalize().ok().as_ref() == Some(&path)
+                    })
+                })
+                .flatten();
+            let Some(mnt) = device.or_else(|| util::get_best_mount_match(&path, mnts)) else {
+                continue;
+            };
+            if !selected(mnt) {
+                continue;
+            }
+            if mnt.statfs.is_none() {
+                let reason = mnt
+                    .status
+                    .errno()
+                    .map_or_else(|| mnt.status.to_string(), strerror);
+                eprintln!("df: {}: {}", file.display(), reason);
+                *failed = true;
+                continue;
+            }
+            rows.push(Row::new(mnt, &file.to_string_lossy()));
+        }
+        rows
+    }
+}
+
+/// Keep a single mount of every device like GNU df, see [`replaces`]. The
+/// device is that of the mount point, as subvolumes differ from the mounted
+/// file system. Remote file systems from different locations are likely
+/// mounted on purpose and only collapsed for the total.
+fn dedupe(mnts: Vec<Mount>, total: bool) -> Vec<Mount> {
+    let key = |mnt: &Mount| {
+        let dev = mnt
+            .statfs
+            .and_then(|_| stat::stat(mnt.mnt_dir.as_str()).ok())?
+            .st_dev;
+        let location = (!total && mnt.is_remote()).then(|| mnt.mnt_fsname.clone());
+        Some((dev, location))
+    };
+    util::dedupe_by(mnts, key, replaces)
+}
+
+/// Whether a mount of an already seen device is shown instead of it: real
+/// devices, mount points nearer the root and mounts on top of others win
+fn replaces(mnt: &Mount, seen: &Mount) -> bool {
+    let nearer_root = seen.mnt_dir.len() > mnt.mnt_dir.len();
+    let below_root = match (&seen.mnt_root, &mnt.mnt_root) {
+        (Some(seen), Some(root)) => seen.len() < root.len(),
+        _ => false,
+    };
+    (mnt.mnt_fsname.contains('/') && !seen.mnt_fsname.contains('/'))
+        || (nearer_root && !below_root)
+        || (seen.mnt_fsname != mnt.mnt_fsname && seen.mnt_dir == mnt.mnt_dir)
+}
+
+/// The block size in the heading, e.g., `1K` or `512B`, in the base that
+/// divides it more often
+fn block_label(size: u64) -> String {
+    let (mut q1000, mut q1024) = (size, size);
+    let (mut by_1000, mut by_1024);
+    loop {
+        by_1000 = q1000.is_multiple_of(1000);
+        q1000 /= 1000;
+        by_1024 = q1024.is_multiple_of(1024);
+        q1024 /= 1024;
+        if !(by_1000 && by_1024) {
+            break;
+        }
+    }
+    if by_1024 && !by_1000 {
+        format_rounded_up(size, 1024, true)
+    } else {
+        format!("{}B", format_rounded_up(size, 1000, true))
+    }
+}
+
+/// Replace control characters, like newlines in mount points, with `?`
+fn printable(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_control() { '?' } else { c })
+        .collect()
+}
+
+fn strerror(errno: Errno) -> String {
+    // SAFETY: strerror returns a valid string, it is copied before any
+    // other call may overwrite it
+    unsafe { CStr::from_ptr(libc::strerror(errno as i32)) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn describe(err: &io::Error) -> String {
+    err.raw_os_error()
+        .map_or_else(|| err.to_string(), |errno| strerror(Errno::from_raw(errno)))
+}
+
+fn write_table<W: Write>(w: &mut W, table: &[Vec<String>], fields: &[Field]) -> io::Result<()> {
+    let widths = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            table
+                .iter()
+                .map(|row| row[i].chars().count())
+                .fold(field.min_width(), usize::max)
+        })
+        .collect::<Vec<_>>();
+
+    for row in table {
+        let mut line = String::new();
+        for (i, (cell, field)) in row.iter().zip(fields).enumerate() {
+            if i > 0 {
+                line.push(' ');
+            }
+            let padding = " ".repeat(widths[i].saturating_sub(cell.chars().count()));
+            if field.right_aligned() {
+                line.push_str(&padding);
+                line.push_str(cell);
+            } else {
+                line.push_str(cell);
+                if i + 1 < fields.len() {
+                    line.push_str(&padding);
+                }
+            }
+        }
+        writeln!(w, "{}", line)?;
+    }
+    w.flush()
+}
+
+/// Whether to behave like GNU df, when invoked as `df` or with
+/// `--df-compat` as the first argument. Later on it may be the value of an
+/// option or a file name after `--`.
+pub fn requested<I: IntoIterator<Item = OsString>>(argv: I) -> bool {
+    let mut argv = argv.into_iter();
+    argv.next()
+        .as_deref()
+        .and_then(|arg0| Path::new(arg0).file_name())
+        .is_some_and(|name| name == "df")
+        || argv.next().is_some_and(|arg| arg == "--df-compat")
+}
+
+fn run(df: &DfArgs, spec: Spec, mode: Mode, fields: &[Field]) -> Result<bool> {
+    let args = df.dfrs_args(spec);
+    let format = NumberFormat {
+        round_up: true,
+        ..number_format(&args)
+    };
+
+    if df.sync {
+        nix::unistd::sync();
+    }
+
+    let query = mount_query(&args, &[]).unsorted();
+    let mut failed = false;
+    let mut rows = if df.files.is_empty() {
+        df.mount_rows(query.run()?, &mut failed)
+    } else {
+        let mnts = query
+            .clone()
+            .mount_filter(MountFilter::default())
+            .local_only(false)
+            .run()?;
+        let selected = |mnt: &Mount| !query.filter(vec![mnt.clone()]).is_empty();
+        df.file_rows(&mnts, selected, &mut failed)
+    };
+
+    if rows.is_empty() {
+        if df.files.is_empty() {
+            bail!("no file systems processed");
+        }
+        return Ok(!failed);
+    }
+    if args.total {
+        let mnts = rows.iter().map(|row| row.mnt.clone()).collect::<Vec<_>>();
+        let mut total = util::calc_total(&mnts);
+        total.mnt_type = "-".to_string();
+        total.mnt_dir = if fields.contains(&Field::Source) {
+            "-"
+        } else {
+            "total"
+        }
+        .to_string();
+        rows.push(Row::new(&total, "-"));
+    }
+
+    let heading = fields
+        .iter()
+        .map(|field| field.heading(mode, spec))
+        .collect();
+    let table = std::iter::once(heading)
+        .chain(rows.iter().map(|row| {
+            fields
+                .iter()
+                .map(|field| field.value(row, spec, &format))
+                .collect()
+        }))
+        .collect::<Vec<Vec<String>>>();
+    let mut w = BufWriter::new(stdout().lock());
+    match write_table(&mut w, &table, fields) {
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => return Err(err.into()),
+        _ => {}
+    }
+    Ok(!failed)
+}
+
+/// Run as GNU df and return the exit code
+pub fn main() -> i32 {
+    let df = match DfArgs::try_parse() {
+        Ok(df) => df,
+        Err(err) => {
+            let _ = err.print();
+            return i32::from(err.use_stderr());
+        }
+    };
+    let spec = df.spec();
+    let mode = df.mode(spec);
+    let fields = match df.fields(mode) {
+        Ok(fields) => fields,
+        Err(err) => {
+            eprintln!("df: {}", err);
+            eprintln!("Try 'df --help' for more information.");
+            return 1;
+        }
+    };
+    match run(&df, spec, mode, &fields) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(err) => {
+            eprintln!("df: {}", err);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_labels() {
+        assert_eq!(block_label(1024), "1K");
+        assert_eq!(block_label(512), "512B");
+        assert_eq!(block_label(1000), "1kB");
+        assert_eq!(block_label(1536), "1.6kB");
+        assert_eq!(block_label(1024 * 1024), "1M");
+        assert_eq!(block_label(1), "1B");
+    }
+
+    #[test]
+    fn values() {
+        let mut mnt = Mount::named("/dev/vda".to_string());
+        mnt.mnt_dir = "/mnt/a\nb".to_string();
+        mnt.statfs = Some(dfrs::mount::StatfsInfo {
+            block_size: 4096,
+            blocks: 66_053_021,
+            blocks_free: 60_967_959,
+            blocks_available: 20_115_507,
+            files: 1000,
+            files_free: 999,
+        });
+        mnt.compute_usage(false);
+        let row = Row::new(&mnt, "-");
+        let spec = Spec::Bytes {
+            size: 1024,
+            grouped: false,
+            suffixed: true,
+        };
+        let format = NumberFormat::new(dfrs::util::Scale::Blocks(1024));
+        let value = |field: Field| field.value(&row, spec, &format);
+        assert_eq!(value(Field::Size), "264212084K");
+        assert_eq!(value(Field::Used), "20340248K");
+        assert_eq!(value(Field::Avail), "80462028K");
+        assert_eq!(value(Field::Pcent), "21%");
+        assert_eq!(value(Field::Iused), "1");
+        assert_eq!(value(Field::Ipcent), "1%");
+        assert_eq!(value(Field::Target), "/mnt/a?b");
+
+        let row = Row::new(&Mount::named("proc".to_string()), "-");
+        assert_eq!(row.mnt.statfs, None);
+        assert_eq!(Field::Size.value(&row, spec, &format), "-");
+    }
+
+    #[test]
+    fn output_fields() {
+        let df = DfArgs::parse_from(["df", "--output=target,pcent", "--output=source"]);
+        assert_eq!(
+            df.fields(Mode::Output).unwrap(),
+            [Field::Target, Field::Pcent, Field::Source]
+        );
+        let df = DfArgs::parse_from(["df", "--output"]);
+        assert_eq!(df.fields(Mode::Output).unwrap(), Field::ALL);
+        let df = DfArgs::parse_from(["df", "--output=size,size"]);
+        assert!(df.fields(Mode::Output).is_err());
+        let df = DfArgs::parse_from(["df", "--output=capacity"]);
+        assert!(df.fields(Mode::Output).is_err());
+    }
+
+    #[test]
+    fn last_unit_option_wins() {
+        let df = DfArgs::parse_from(["df", "-h", "-k"]);
+        assert_eq!(
+            df.spec(),
+            Spec::Bytes {
+                size: 1024,
+                grouped: false,
+                suffixed: false,
+            }
+        );
+        let df = DfArgs::parse_from(["df", "-BM", "-P", "-h"]);
+        assert_eq!(df.spec(), Spec::Human);
+        assert_eq!(df.mode(df.spec()), Mode::Human);
+        let df = DfArgs::parse_from(["df", "-P", "-B", "'1K"]);
+        assert_eq!(
+            df.spec(),
+            Spec::Bytes {
+                size: 1024,
+                grouped: true,
+                suffixed: false,
+            }
+        );
+        let df = DfArgs::parse_from(["df", "-BKB"]);
+        assert_eq!(
+            df.spec(),
+            Spec::Bytes {
+                size: 1000,
+                grouped: false,
+                suffixed: true,
+            }
+        );
+    }
+
+    #[test]
+    fn posix_heading() {
+        let fields = [Field::Source, Field::Size, Field::Avail, Field::Pcent];
+        let spec = Spec::Bytes {
+            size: 1024,
+            grouped: false,
+            suffixed: false,
+        };
+        let heading = fields
+            .iter()
+            .map(|field| field.heading(Mode::Posix, spec))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            heading,
+            ["Filesystem", "1024-blocks", "Available", "Capacity"]
+        );
+
+        let table = vec![
+            heading,
+            vec![
+                "/dev/vda".to_string(),
+                "264212084".to_string(),
+                "80462028".to_string(),
+                "21%".to_string(),
+            ],
+        ];
+        let mut out = Vec::new();
+        write_table(&mut out, &table, &fields).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Filesystem     1024-blocks Available Capacity\n\
+             /dev/vda         264212084  80462028      21%\n"
+        );
+    }
+
+    #[test]
+    fn requested_as_df_or_leading_flag() {
+        let requested = |argv: &[&str]| requested(argv.iter().map(OsString::from));
+        assert!(requested(&["/usr/bin/df", "-h"]));
+        assert!(requested(&["dfrs", "--df-compat", "-h"]));
+        assert!(!requested(&["dfrs", "-h"]));
+        assert!(!requested(&["dfrs", "--filter", "--df-compat"]));
+        assert!(!requested(&["dfrs", "--", "--df-compat"]));
+        assert!(!requested(&["dfrs-df"]));
+    }
+
+    #[test]
+    fn dedupe_prefers_real_devices() {
+        let mut seen = Mount::named("overlay".to_string());
+        seen.mnt_dir = "/srv".to_string();
+        let mut mnt = Mount::named("/dev/sda1".to_string());
+        mnt.mnt_dir = "/var".to_string();
+        assert!(replaces(&mnt, &seen));
+        assert!(!replaces(&seen, &mnt));
+    }
+}
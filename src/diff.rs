@@ -0,0 +1,196 @@
+use crate::mount::Mount;
+
+/// How a mount differs between two points in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffStatus {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+/// A mount before and after, with the side it is missing from left empty.
+#[derive(Debug, Clone)]
+pub struct MountDiff {
+    pub old: Option<Mount>,
+    pub new: Option<Mount>,
+}
+
+impl MountDiff {
+    /// The most recent state of the mount
+    pub fn mount(&self) -> &Mount {
+        self.new
+            .as_ref()
+            .or(self.old.as_ref())
+            .expect("diff of neither an old nor a new mount")
+    }
+
+    pub fn status(&self) -> DiffStatus {
+        match (&self.old, &self.new) {
+            (None, _) => DiffStatus::Added,
+            (_, None) => DiffStatus::Removed,
+            (Some(old), Some(new))
+                if old.mnt_fsname == new.mnt_fsname
+                    && old.capacity == new.capacity
+                    && old.used == new.used
+                    && old.available == new.available
+                    && inodes_used(old) == inodes_used(new) =>
+            {
+                DiffStatus::Unchanged
+            }
+            _ => DiffStatus::Changed,
+        }
+    }
+
+    /// Difference of a value, a missing side counts as zero
+    fn delta<F: Fn(&Mount) -> u64>(&self, f: F) -> i64 {
+        let value = |mnt: &Option<Mount>| mnt.as_ref().map_or(0, &f) as i64;
+        value(&self.new) - value(&self.old)
+    }
+
+    pub fn used_delta(&self) -> i64 {
+        self.delta(|mnt| mnt.used)
+    }
+
+    pub fn free_delta(&self) -> i64 {
+        self.delta(|mnt| mnt.available)
+    }
+
+    pub fn capacity_delta(&self) -> i64 {
+        self.delta(|mnt| mnt.capacity)
+    }
+
+    pub fn inodes_used_delta(&self) -> i64 {
+        self.delta(inodes_used)
+    }
+
+    /// Change of the used percentage in percentage points
+    pub fn used_percentage_delta(&self) -> Option<f32> {
+        let old = self.old.as_ref()?.used_percentage()?;
+        let new = self.new.as_ref()?.used_percentage()?;
+        Some(new - old)
+    }
+}
+
+fn inodes_used(mnt: &Mount) -> u64 {
+    mnt.statfs
+        .map_or(0, |stat| stat.files.saturating_sub(stat.files_free))
+}
+
+/// Pair up the mounts of two states by their mount point, ordered by mount
+/// point.
+///
+/// Each old mount pairs with at most one new mount, preferably one of the
+/// same file system, so mounts stacked on the same mount point pair up with
+/// each other in order. A file system replaced by another on the same mount
+/// point is a change.
+pub fn diff_mounts(old: &[Mount], new: &[Mount]) -> Vec<MountDiff> {
+    let mut unpaired = old.iter().map(Some).collect::<Vec<_>>();
+    let mut pair = |same: &dyn Fn(&Mount) -> bool| {
+        unpaired
+            .iter_mut()
+            .find(|o| o.is_some_and(same))
+            .and_then(Option::take)
+            .cloned()
+    };
+    let mut olds = new
+        .iter()
+        .map(|mnt| pair(&|o| o.mnt_dir == mnt.mnt_dir && o.mnt_fsname == mnt.mnt_fsname))
+        .collect::<Vec<_>>();
+    for (mnt, old) in new.iter().zip(&mut olds) {
+        if old.is_none() {
+            *old = pair(&|o| o.mnt_dir == mnt.mnt_dir);
+        }
+    }
+
+    let mut diffs = new
+        .iter()
+        .zip(olds)
+        .map(|(mnt, old)| MountDiff {
+            old,
+            new: Some(mnt.clone()),
+        })
+        .collect::<Vec<_>>();
+    diffs.extend(unpaired.into_iter().flatten().map(|mnt| MountDiff {
+        old: Some(mnt.clone()),
+        new: None,
+    }));
+    diffs.sort_by(|a, b| a.mount().mnt_dir.cmp(&b.mount().mnt_dir));
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mount::StatfsInfo;
+
+    fn mount(dir: &str, blocks_available: u64, files_free: u64) -> Mount {
+        let mut mnt = Mount::named("/dev/sda1".into());
+        mnt.mnt_dir = dir.to_string();
+        mnt.statfs = Some(StatfsInfo {
+            block_size: 1,
+            blocks: 1000,
+            blocks_free: blocks_available,
+            blocks_available,
+            files: 100,
+            files_free,
+        });
+        mnt.compute_usage(false);
+        mnt
+    }
+
+    #[test]
+    fn diff_changed_and_unchanged() {
+        let old = [mount("/", 500, 50), mount("/srv", 500, 50)];
+        let new = [mount("/", 400, 40), mount("/srv", 500, 50)];
+        let diffs = diff_mounts(&old, &new);
+        assert_eq!(diffs.len(), 2);
+
+        assert_eq!(diffs[0].status(), DiffStatus::Changed);
+        assert_eq!(diffs[0].used_delta(), 100);
+        assert_eq!(diffs[0].free_delta(), -100);
+        assert_eq!(diffs[0].capacity_delta(), 0);
+        assert_eq!(diffs[0].inodes_used_delta(), 10);
+        assert_eq!(diffs[0].used_percentage_delta(), Some(10.0));
+
+        assert_eq!(diffs[1].status(), DiffStatus::Unchanged);
+        assert_eq!(diffs[1].used_percentage_delta(), Some(0.0));
+    }
+
+    #[test]
+    fn diff_added_and_removed() {
+        let old = [mount("/", 500, 50), mount("/mnt/old", 500, 50)];
+        let new = [mount("/mnt/new", 900, 50), mount("/", 500, 50)];
+        let diffs = diff_mounts(&old, &new);
+        let dirs = diffs
+            .iter()
+            .map(|diff| diff.mount().mnt_dir.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(dirs, ["/", "/mnt/new", "/mnt/old"]);
+
+        assert_eq!(diffs[1].status(), DiffStatus::Added);
+        assert_eq!(diffs[1].used_delta(), 100);
+        assert_eq!(diffs[1].used_percentage_delta(), None);
+        assert_eq!(diffs[2].status(), DiffStatus::Removed);
+        assert_eq!(diffs[2].capacity_delta(), -1000);
+    }
+
+    #[test]
+    fn diff_stacked_mounts() {
+        let mut covered = mount("/srv", 500, 50);
+        covered.mnt_fsname = "/dev/sdb1".into();
+        let old = [covered, mount("/srv", 500, 50)];
+
+        let diffs = diff_mounts(&old, &[mount("/srv", 500, 50)]);
+        let statuses = diffs.iter().map(MountDiff::status).collect::<Vec<_>>();
+        assert_eq!(statuses, [DiffStatus::Unchanged, DiffStatus::Removed]);
+        assert_eq!(diffs[1].mount().mnt_fsname, "/dev/sdb1");
+
+        let mut replaced = mount("/srv", 400, 50);
+        replaced.mnt_fsname = "/dev/sdc1".into();
+        let diffs = diff_mounts(&old, &[mount("/srv", 500, 50), replaced]);
+        let statuses = diffs.iter().map(MountDiff::status).collect::<Vec<_>>();
+        assert_eq!(statuses, [DiffStatus::Unchanged, DiffStatus::Changed]);
+        assert_eq!(diffs[1].old.as_ref().unwrap().mnt_fsname, "/dev/sdb1");
+    }
+}
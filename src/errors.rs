@@ -1,2 +1,104 @@
-pub use anyhow::{anyhow, Context, Error, Result};
-pub use log::debug;
+use std::io;
+use std::num::ParseIntError;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to open mount table {}", path.display())]
+    OpenMountTable {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to read mount table")]
+    ReadMountTable(#[source] io::Error),
+    #[error("Missing value {0}")]
+    MissingField(&'static str),
+    #[error("Invalid value {field}: {value}")]
+    InvalidField {
+        field: &'static str,
+        value: String,
+        #[source]
+        source: Option<ParseIntError>,
+    },
+    #[error("Invalid size {0}")]
+    InvalidSize(String),
+    #[error("Failed to read history {}", path.display())]
+    ReadHistory {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to write history {}", path.display())]
+    WriteHistory {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to read snapshot {}", path.display())]
+    ReadSnapshot {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[cfg(feature = "serde")]
+    #[error("Invalid snapshot {}", path.display())]
+    InvalidSnapshot {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("Unsupported snapshot schema version {0}")]
+    UnsupportedSnapshot(u32),
+    #[error("Failed to read config {}", path.display())]
+    ReadConfig {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[cfg(feature = "serde")]
+    #[error("Invalid config {}", path.display())]
+    InvalidConfig {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+    #[error("Invalid config {}: {key}: {message}", path.display())]
+    InvalidConfigValue {
+        path: PathBuf,
+        key: String,
+        message: String,
+    },
+    #[error("Unknown profile {0}")]
+    UnknownProfile(String),
+    #[error("Invalid expression: {message}\n  {expression}\n  {}", caret(expression, *start, *end))]
+    InvalidExpression {
+        expression: String,
+        message: String,
+        /// Byte offsets of the offending part of the expression
+        start: usize,
+        end: usize,
+    },
+    #[error("{}: {}", path.display(), source)]
+    Path {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+/// Underline the bytes `start..end` of the expression printed above
+fn caret(expression: &str, start: usize, end: usize) -> String {
+    let width = |s: &str| s.chars().count();
+    let end = end.min(expression.len());
+    let start = start.min(end);
+    format!(
+        "{}{}",
+        " ".repeat(width(&expression[..start])),
+        "^".repeat(width(&expression[start..end]).max(1))
+    )
+}
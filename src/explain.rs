@@ -0,0 +1,174 @@
+use dfrs::query::Explanation;
+use dfrs::theme::Theme;
+
+use colored::*;
+use serde_json::json;
+use std::io::{self, Write};
+
+/// Result of querying the usage, if it was queried
+fn statfs_result(explanation: &Explanation) -> Option<String> {
+    let mnt = &explanation.mount;
+    (!mnt.status.is_ok() || mnt.statfs.is_some()).then(|| mnt.status.to_string())
+}
+
+/// Print every mount with the rule that shows or hides it as a table
+pub fn display_explanations<W: Write + ?Sized>(
+    w: &mut W,
+    explanations: &[Explanation],
+    theme: &Theme,
+) -> io::Result<()> {
+    let color_heading = theme.color_heading.unwrap_or(Color::White);
+    let color_hidden = theme.color_usage_void.unwrap_or(Color::Blue);
+
+    let rows = explanations
+        .iter()
+        .map(|explanation| {
+            [
+                explanation.mount.mnt_dir.clone(),
+                explanation.mount.mnt_fsname.clone(),
+                explanation.mount.mnt_type.clone(),
+                if explanation.shown { "yes" } else { "no" }.to_string(),
+                statfs_result(explanation).unwrap_or_else(|| "-".to_string()),
+                explanation.rule.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let headings = [
+        "Mounted on",
+        "File system",
+        "Type",
+        "Shown",
+        "Statfs",
+        "Rule",
+    ];
+    let widths = headings
+        .iter()
+        .enumerate()
+        .map(|(idx, heading)| {
+            rows.iter()
+                .map(|row| row[idx].chars().count())
+                .chain(std::iter::once(heading.len()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    let mut line = String::new();
+    for (heading, width) in headings.iter().zip(&widths) {
+        line.push_str(&format!(
+            "{:<width$} ",
+            heading.color(color_heading),
+            width = width
+        ));
+    }
+    writeln!(w, "{}", line.trim_end())?;
+
+    for (explanation, row) in explanations.iter().zip(&rows) {
+        let mut line = String::new();
+        for (value, width) in row.iter().zip(&widths) {
+            line.push_str(&format!("{:<width$} ", value, width = width));
+        }
+        let line = line.trim_end();
+        if explanation.shown {
+            writeln!(w, "{}", line)?;
+        } else {
+            writeln!(w, "{}", line.color(color_hidden))?;
+        }
+    }
+    w.flush()
+}
+
+/// Print every mount with the rule that shows or hides it as a json array,
+/// or as one object per line
+pub fn write_explanations_json<W: Write + ?Sized>(
+    w: &mut W,
+    explanations: &[Explanation],
+    ndjson: bool,
+) -> io::Result<()> {
+    let values = explanations.iter().map(|explanation| {
+        json!({
+            "fsname": explanation.mount.mnt_fsname,
+            "dir": explanation.mount.mnt_dir,
+            "type": explanation.mount.mnt_type,
+            "shown": explanation.shown,
+            "rule": explanation.rule.name(),
+            "reason": explanation.rule.to_string(),
+            "statfs": statfs_result(explanation),
+        })
+    });
+    if ndjson {
+        for value in values {
+            serde_json::to_writer(&mut *w, &value)?;
+            writeln!(w)?;
+        }
+    } else {
+        serde_json::to_writer_pretty(&mut *w, &values.collect::<Vec<_>>())?;
+        writeln!(w)?;
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfrs::filter::DisplayFilter;
+    use dfrs::mount::Status;
+    use dfrs::query::Rule;
+    use dfrs::Mount;
+    use nix::errno::Errno;
+
+    fn explanations() -> Vec<Explanation> {
+        let mut root = Mount::named("/dev/sda1".into());
+        root.mnt_dir = "/".to_string();
+        root.mnt_type = "ext4".to_string();
+        root.statfs = Some(Default::default());
+        let mut proc = Mount::named("proc".into());
+        proc.mnt_dir = "/proc".to_string();
+        proc.mnt_type = "proc".to_string();
+        proc.status = Status::Failed(Errno::EACCES);
+        vec![
+            Explanation {
+                mount: root,
+                shown: true,
+                rule: Rule::DisplayFilter(DisplayFilter::Minimal, Some("/dev*")),
+            },
+            Explanation {
+                mount: proc,
+                shown: false,
+                rule: Rule::DisplayFilter(DisplayFilter::Minimal, None),
+            },
+        ]
+    }
+
+    #[test]
+    fn explain_table() {
+        colored::control::set_override(false);
+        let mut out = Vec::new();
+        display_explanations(&mut out, &explanations(), &Theme::new()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "Mounted on File system Type Shown Statfs            Rule",
+                "/          /dev/sda1   ext4 yes   ok                display filter minimal: name matches /dev*",
+                "/proc      proc        proc no    Permission denied display filter minimal: name matches none of /dev*, storage",
+            ]
+        );
+    }
+
+    #[test]
+    fn explain_ndjson() {
+        let mut out = Vec::new();
+        write_explanations_json(&mut out, &explanations(), true).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let values = out
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(values[0]["shown"], true);
+        assert_eq!(values[0]["statfs"], "ok");
+        assert_eq!(values[1]["rule"], "display_filter");
+        assert_eq!(values[1]["statfs"], "Permission denied");
+    }
+}
@@ -0,0 +1,716 @@
+//! Predicates over mounts as used by `--where`, e.g.
+//! `used_percentage > 80 && type in ["ext4", "xfs"] && !mounted_on ~ "^/snap"`.
+//!
+//! Comparisons take a field on the left and a literal on the right. Numbers
+//! may carry a `%` or a size suffix in powers of 1024 (e.g., `10G`), strings
+//! are quoted with `"` or `'`, `~` matches a regular expression and `in`
+//! tests against a list. Option flags of the mount are available as
+//! booleans named `opt.<flag>` (e.g., `opt.ro`). Comparisons against a value
+//! a mount does not have, like the used percentage of a file system without
+//! a size, never match.
+
+use crate::errors::{Error, Result};
+use crate::mount::Mount;
+use crate::util::parse_size;
+
+use regex::Regex;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+type Span = Range<usize>;
+type ParseResult<T> = std::result::Result<T, (String, Span)>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Str(String),
+    True,
+    False,
+    In,
+    And,
+    Or,
+    Not,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+}
+
+fn tokenize(input: &str) -> ParseResult<Vec<(Token, Span)>> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while let Some(c) = input[pos..].chars().next() {
+        let start = pos;
+        if c.is_whitespace() {
+            pos += c.len_utf8();
+            continue;
+        }
+        let next = bytes.get(pos + 1).copied();
+        let (token, len) = match (c, next) {
+            ('&', Some(b'&')) => (Token::And, 2),
+            ('|', Some(b'|')) => (Token::Or, 2),
+            ('=', Some(b'=')) => (Token::Eq, 2),
+            ('!', Some(b'=')) => (Token::Ne, 2),
+            ('<', Some(b'=')) => (Token::Le, 2),
+            ('>', Some(b'=')) => (Token::Ge, 2),
+            ('!', _) => (Token::Not, 1),
+            ('<', _) => (Token::Lt, 1),
+            ('>', _) => (Token::Gt, 1),
+            ('~', _) => (Token::Match, 1),
+            ('(', _) => (Token::LParen, 1),
+            (')', _) => (Token::RParen, 1),
+            ('[', _) => (Token::LBracket, 1),
+            (']', _) => (Token::RBracket, 1),
+            (',', _) => (Token::Comma, 1),
+            ('"' | '\'', _) => {
+                let (value, len) = lex_string(&input[pos..], c)
+                    .map_err(|message| (message, start..input.len()))?;
+                (Token::Str(value), len)
+            }
+            (c, _) if c.is_ascii_digit() || c == '.' => {
+                let len = input[pos..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '%'))
+                    .unwrap_or(input.len() - pos);
+                let text = &input[pos..pos + len];
+                (
+                    Token::Number(
+                        lex_number(text).map_err(|message| (message, start..start + len))?,
+                    ),
+                    len,
+                )
+            }
+            (c, _) if c.is_ascii_alphabetic() || c == '_' => {
+                let len = input[pos..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || "_.-".contains(c)))
+                    .unwrap_or(input.len() - pos);
+                let token = match &input[pos..pos + len] {
+                    "in" => Token::In,
+                    "true" => Token::True,
+                    "false" => Token::False,
+                    ident => Token::Ident(ident.to_string()),
+                };
+                (token, len)
+            }
+            (c, _) => {
+                return Err((
+                    format!("unexpected character '{}'", c),
+                    start..start + c.len_utf8(),
+                ))
+            }
+        };
+        pos += len;
+        tokens.push((token, start..pos));
+    }
+    Ok(tokens)
+}
+
+/// Read a quoted string with `\` escapes, returning its value and length
+fn lex_string(input: &str, quote: char) -> std::result::Result<(String, usize), String> {
+    let mut value = String::new();
+    let mut chars = input.char_indices().skip(1);
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, escaped)) => value.push(escaped),
+                None => break,
+            },
+            c if c == quote => return Ok((value, idx + 1)),
+            c => value.push(c),
+        }
+    }
+    Err("unterminated string".to_string())
+}
+
+fn lex_number(text: &str) -> std::result::Result<f64, String> {
+    let invalid = || format!("invalid number {}", text);
+    if let Some(percentage) = text.strip_suffix('%') {
+        return percentage.parse::<f64>().map_err(|_| invalid());
+    }
+    if text.contains(|c: char| c.is_ascii_alphabetic()) {
+        return parse_size(text)
+            .map(|size| size as f64)
+            .map_err(|_| format!("invalid size {}", text));
+    }
+    text.parse::<f64>().map_err(|_| invalid())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Str,
+    Num,
+    Bool,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Str => "a string",
+            Self::Num => "a number",
+            Self::Bool => "a boolean",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Filesystem,
+    Type,
+    MountedOn,
+    Options,
+    Host,
+    Status,
+    Capacity,
+    Used,
+    Available,
+    Reserved,
+    Free,
+    UsedPercentage,
+    AvailablePercentage,
+    Inodes,
+    InodesUsed,
+    InodesFree,
+    InodesUsedPercentage,
+    IsRemote,
+    IsLocal,
+    Flag(String),
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        if let Some(flag) = name.strip_prefix("opt.") {
+            return (!flag.is_empty()).then(|| Self::Flag(flag.to_string()));
+        }
+        Some(match name {
+            "filesystem" | "fsname" => Self::Filesystem,
+            "type" => Self::Type,
+            "mounted_on" | "dir" => Self::MountedOn,
+            "options" => Self::Options,
+            "host" => Self::Host,
+            "status" => Self::Status,
+            "capacity" | "size" => Self::Capacity,
+            "used" => Self::Used,
+            "available" | "avail" => Self::Available,
+            "reserved" => Self::Reserved,
+            "free" => Self::Free,
+            "used_percentage" => Self::UsedPercentage,
+            "available_percentage" => Self::AvailablePercentage,
+            "inodes" => Self::Inodes,
+            "inodes_used" => Self::InodesUsed,
+            "inodes_free" => Self::InodesFree,
+            "inodes_used_percentage" => Self::InodesUsedPercentage,
+            "is_remote" => Self::IsRemote,
+            "is_local" => Self::IsLocal,
+            _ => return None,
+        })
+    }
+
+    const fn kind(&self) -> Kind {
+        match self {
+            Self::Filesystem
+            | Self::Type
+            | Self::MountedOn
+            | Self::Options
+            | Self::Host
+            | Self::Status => Kind::Str,
+            Self::IsRemote | Self::IsLocal | Self::Flag(_) => Kind::Bool,
+            _ => Kind::Num,
+        }
+    }
+
+    fn value(&self, mnt: &Mount) -> Value {
+        let stat = mnt.statfs.unwrap_or_default();
+        let inodes_used = stat.files.saturating_sub(stat.files_free);
+        match self {
+            Self::Filesystem => Value::Str(mnt.mnt_fsname.clone()),
+            Self::Type => Value::Str(mnt.mnt_type.clone()),
+            Self::MountedOn => Value::Str(mnt.mnt_dir.clone()),
+            Self::Options => Value::Str(mnt.mnt_opts.clone()),
+            Self::Host => Value::Str(mnt.host_formatted()),
+            Self::Status => Value::Str(mnt.status.name().to_string()),
+            Self::Capacity => Value::Num(Some(mnt.capacity as f64)),
+            Self::Used => Value::Num(Some(mnt.used as f64)),
+            Self::Available => Value::Num(Some(mnt.available as f64)),
+            Self::Reserved => Value::Num(Some(mnt.reserved as f64)),
+            Self::Free => Value::Num(Some(mnt.free() as f64)),
+            Self::UsedPercentage => Value::Num(mnt.used_percentage().map(f64::from)),
+            Self::AvailablePercentage => Value::Num(mnt.available_percentage().map(f64::from)),
+            Self::Inodes => Value::Num(mnt.statfs.map(|_| stat.files as f64)),
+            Self::InodesUsed => Value::Num(mnt.statfs.map(|_| inodes_used as f64)),
+            Self::InodesFree => Value::Num(mnt.statfs.map(|_| stat.files_free as f64)),
+            Self::InodesUsedPercentage => {
+                Value::Num((stat.files > 0).then(|| inodes_used as f64 * 100.0 / stat.files as f64))
+            }
+            Self::IsRemote => Value::Bool(mnt.is_remote()),
+            Self::IsLocal => Value::Bool(mnt.is_local()),
+            Self::Flag(flag) => Value::Bool(
+                mnt.mnt_opts
+                    .split(',')
+                    .chain(mnt.mnt_super_opts.iter().flat_map(|opts| opts.split(',')))
+                    .any(|opt| opt.split('=').next() == Some(flag)),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Str(String),
+    /// None if the mount has no such value
+    Num(Option<f64>),
+    Bool(bool),
+}
+
+impl Value {
+    const fn kind(&self) -> Kind {
+        match self {
+            Self::Str(_) => Kind::Str,
+            Self::Num(_) => Kind::Num,
+            Self::Bool(_) => Kind::Bool,
+        }
+    }
+
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Str(a), Self::Str(b)) => Some(a.cmp(b)),
+            (Self::Num(Some(a)), Self::Num(Some(b))) => a.partial_cmp(b),
+            (Self::Bool(a), Self::Bool(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Eq => ordering == Ordering::Equal,
+            Self::Ne => ordering != Ordering::Equal,
+            Self::Lt => ordering == Ordering::Less,
+            Self::Le => ordering != Ordering::Greater,
+            Self::Gt => ordering == Ordering::Greater,
+            Self::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Const(bool),
+    Flag(Field),
+    Compare(Field, Op, Value),
+    In(Field, Vec<Value>),
+    Match(Field, Regex),
+    Not(Box<Self>),
+    And(Box<Self>, Box<Self>),
+    Or(Box<Self>, Box<Self>),
+}
+
+impl Node {
+    fn eval(&self, mnt: &Mount) -> bool {
+        match self {
+            Self::Const(value) => *value,
+            Self::Flag(field) => field.value(mnt) == Value::Bool(true),
+            Self::Compare(field, op, value) => field
+                .value(mnt)
+                .compare(value)
+                .is_some_and(|ordering| op.holds(ordering)),
+            Self::In(field, values) => {
+                let value = field.value(mnt);
+                values
+                    .iter()
+                    .any(|v| value.compare(v) == Some(Ordering::Equal))
+            }
+            Self::Match(field, regex) => match field.value(mnt) {
+                Value::Str(value) => regex.is_match(&value),
+                _ => false,
+            },
+            Self::Not(node) => !node.eval(mnt),
+            Self::And(a, b) => a.eval(mnt) && b.eval(mnt),
+            Self::Or(a, b) => a.eval(mnt) || b.eval(mnt),
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<(Token, Span)>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    /// Span of the current token, or just past the end of the input
+    fn span(&self) -> Span {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.input.len()..self.input.len() + 1, |(_, span)| {
+                span.clone()
+            })
+    }
+
+    fn advance(&mut self) -> Option<(Token, Span)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn error<T>(&self, message: &str) -> ParseResult<T> {
+        let found = if self.pos < self.tokens.len() {
+            format!("{}, found '{}'", message, &self.input[self.span()])
+        } else {
+            format!("{}, found end of expression", message)
+        };
+        Err((found, self.span()))
+    }
+
+    fn expect(&mut self, token: Token, message: &str) -> ParseResult<()> {
+        if self.peek() == Some(&token) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.error(message)
+        }
+    }
+
+    fn parse(&mut self) -> ParseResult<Node> {
+        let node = self.parse_or()?;
+        if self.pos < self.tokens.len() {
+            return self.error("expected && or ||");
+        }
+        Ok(node)
+    }
+
+    fn parse_or(&mut self) -> ParseResult<Node> {
+        let mut node = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            node = Node::Or(Box::new(node), Box::new(self.parse_and()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_and(&mut self) -> ParseResult<Node> {
+        let mut node = self.parse_not()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            node = Node::And(Box::new(node), Box::new(self.parse_not()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_not(&mut self) -> ParseResult<Node> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Node::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> ParseResult<Node> {
+        let span = self.span();
+        let name = match self.peek() {
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let node = self.parse_or()?;
+                self.expect(Token::RParen, "expected )")?;
+                return Ok(node);
+            }
+            Some(Token::True) => {
+                self.pos += 1;
+                return Ok(Node::Const(true));
+            }
+            Some(Token::False) => {
+                self.pos += 1;
+                return Ok(Node::Const(false));
+            }
+            Some(Token::Ident(name)) => name.clone(),
+            _ => return self.error("expected a field"),
+        };
+        let field = Field::from_name(&name)
+            .ok_or_else(|| (format!("unknown field {}", name), span.clone()))?;
+        self.pos += 1;
+
+        let op_span = self.span();
+        let op = match self.peek() {
+            Some(Token::Eq) => Op::Eq,
+            Some(Token::Ne) => Op::Ne,
+            Some(Token::Lt) => Op::Lt,
+            Some(Token::Le) => Op::Le,
+            Some(Token::Gt) => Op::Gt,
+            Some(Token::Ge) => Op::Ge,
+            Some(Token::In) => {
+                self.pos += 1;
+                return self.parse_list(field, &name);
+            }
+            Some(Token::Match) => {
+                self.pos += 1;
+                return self.parse_match(field, &name, span);
+            }
+            _ if field.kind() == Kind::Bool => return Ok(Node::Flag(field)),
+            _ => return self.error(&format!("expected a comparison of {}", name)),
+        };
+        self.pos += 1;
+        if field.kind() == Kind::Bool && !matches!(op, Op::Eq | Op::Ne) {
+            return Err((
+                format!("{} can only be compared with == or !=", name),
+                op_span,
+            ));
+        }
+        let value = self.parse_value(&field, &name)?;
+        Ok(Node::Compare(field, op, value))
+    }
+
+    fn parse_value(&mut self, field: &Field, name: &str) -> ParseResult<Value> {
+        let span = self.span();
+        let value = match self.peek() {
+            Some(Token::Number(n)) => Value::Num(Some(*n)),
+            Some(Token::Str(s)) => Value::Str(s.clone()),
+            Some(Token::True) => Value::Bool(true),
+            Some(Token::False) => Value::Bool(false),
+            _ => return self.error("expected a value"),
+        };
+        if value.kind() != field.kind() {
+            return Err((
+                format!("expected {} to compare {} with", field.kind(), name),
+                span,
+            ));
+        }
+        self.pos += 1;
+        Ok(value)
+    }
+
+    fn parse_list(&mut self, field: Field, name: &str) -> ParseResult<Node> {
+        self.expect(Token::LBracket, "expected [")?;
+        let mut values = Vec::new();
+        while self.peek() != Some(&Token::RBracket) {
+            values.push(self.parse_value(&field, name)?);
+            if self.peek() != Some(&Token::RBracket) {
+                self.expect(Token::Comma, "expected , or ]")?;
+            }
+        }
+        self.pos += 1;
+        Ok(Node::In(field, values))
+    }
+
+    fn parse_match(&mut self, field: Field, name: &str, field_span: Span) -> ParseResult<Node> {
+        if field.kind() != Kind::Str {
+            return Err((format!("{} is not a string", name), field_span));
+        }
+        match self.advance() {
+            Some((Token::Str(pattern), span)) => Regex::new(&pattern)
+                .map(|regex| Node::Match(field, regex))
+                .map_err(|err| (format!("invalid regular expression: {}", err), span)),
+            _ => {
+                self.pos -= 1;
+                self.error("expected a quoted regular expression")
+            }
+        }
+    }
+}
+
+/// A parsed `--where` expression
+#[derive(Debug, Clone)]
+pub struct Expr {
+    source: String,
+    root: Node,
+}
+
+impl Expr {
+    pub fn matches(&self, mnt: &Mount) -> bool {
+        self.root.eval(mnt)
+    }
+}
+
+impl FromStr for Expr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |(message, span): (String, Span)| Error::InvalidExpression {
+            expression: s.to_string(),
+            message,
+            start: span.start,
+            end: span.end,
+        };
+        let tokens = tokenize(s).map_err(invalid)?;
+        let mut parser = Parser {
+            input: s,
+            tokens,
+            pos: 0,
+        };
+        let root = parser.parse().map_err(invalid)?;
+        Ok(Self {
+            source: s.to_string(),
+            root,
+        })
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mount::StatfsInfo;
+
+    fn mount(fsname: &str, dir: &str, mnt_type: &str, blocks_available: u64) -> Mount {
+        let mut mnt = Mount::named(fsname.into());
+        mnt.mnt_dir = dir.to_string();
+        mnt.mnt_type = mnt_type.to_string();
+        mnt.mnt_opts = "rw,noexec,commit=30".to_string();
+        mnt.statfs = Some(StatfsInfo {
+            block_size: 1024 * 1024,
+            blocks: 100,
+            blocks_free: blocks_available,
+            blocks_available,
+            files: 1000,
+            files_free: 100,
+        });
+        mnt.compute_usage(false);
+        mnt
+    }
+
+    fn matches(expr: &str, mnt: &Mount) -> bool {
+        expr.parse::<Expr>().unwrap().matches(mnt)
+    }
+
+    fn error(expr: &str) -> (String, usize, usize) {
+        match expr.parse::<Expr>() {
+            Err(Error::InvalidExpression {
+                message,
+                start,
+                end,
+                ..
+            }) => (message, start, end),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn expr_example() {
+        let expr = r#"used_percentage > 80 && type in ["ext4","xfs"] && !mounted_on ~ "^/snap""#;
+        assert!(matches(expr, &mount("/dev/sda1", "/", "ext4", 10)));
+        assert!(!matches(expr, &mount("/dev/sda1", "/", "ext4", 50)));
+        assert!(!matches(expr, &mount("/dev/sda1", "/", "btrfs", 10)));
+        assert!(!matches(
+            expr,
+            &mount("/dev/loop1", "/snap/core", "xfs", 10)
+        ));
+    }
+
+    #[test]
+    fn expr_sizes_and_percentages() {
+        let mnt = mount("/dev/sda1", "/", "ext4", 10);
+        assert!(matches("capacity == 100M", &mnt));
+        assert!(matches("available < 0.5GiB && used >= 90MB", &mnt));
+        assert!(matches("used_percentage >= 90%", &mnt));
+        assert!(matches("inodes_used_percentage == 90", &mnt));
+        assert!(matches("inodes_free == 100 && inodes > 999", &mnt));
+    }
+
+    #[test]
+    fn expr_flags_and_precedence() {
+        let mnt = mount("server:/export", "/mnt/nfs", "nfs4", 10);
+        assert!(matches("opt.noexec && opt.commit && !opt.ro", &mnt));
+        assert!(matches("is_remote == true && !is_local", &mnt));
+        assert!(matches("false || true && opt.rw", &mnt));
+        assert!(!matches("(false || true) && opt.ro", &mnt));
+        assert!(matches("fsname ~ ':' && dir != '/'", &mnt));
+    }
+
+    #[test]
+    fn expr_missing_values_never_match() {
+        let mut mnt = mount("proc", "/proc", "proc", 0);
+        mnt.statfs = None;
+        mnt.compute_usage(false);
+        assert!(!matches("used_percentage < 50", &mnt));
+        assert!(!matches("used_percentage >= 50", &mnt));
+        assert!(!matches("inodes >= 0", &mnt));
+        assert!(matches("!(used_percentage < 50)", &mnt));
+
+        mnt.status = crate::mount::Status::StaleHandle;
+        assert!(matches("status in ['stale', 'stale_handle']", &mnt));
+    }
+
+    #[test]
+    fn expr_errors_point_at_the_bad_part() {
+        assert_eq!(
+            error("used > && type == 'ext4'"),
+            ("expected a value, found '&&'".to_string(), 7, 9)
+        );
+        assert_eq!(
+            error("used_percent > 80"),
+            ("unknown field used_percent".to_string(), 0, 12)
+        );
+        assert_eq!(
+            error("type == 80"),
+            ("expected a string to compare type with".to_string(), 8, 10)
+        );
+        assert_eq!(error("used > 10X"), ("invalid size 10X".to_string(), 7, 10));
+        assert_eq!(
+            error("dir ~ '('"),
+            (
+                "invalid regular expression: regex parse error:\n    (\n    ^\nerror: unclosed group"
+                    .to_string(),
+                6,
+                9
+            )
+        );
+        assert_eq!(
+            error("opt.ro > 1"),
+            (
+                "opt.ro can only be compared with == or !=".to_string(),
+                7,
+                8
+            )
+        );
+        assert_eq!(
+            error("(used > 1"),
+            ("expected ), found end of expression".to_string(), 9, 10)
+        );
+        assert_eq!(
+            error("used > 1 type"),
+            ("expected && or ||, found 'type'".to_string(), 9, 13)
+        );
+        assert_eq!(
+            error("dir == 'foo"),
+            ("unterminated string".to_string(), 7, 11)
+        );
+    }
+
+    #[test]
+    fn expr_error_display() {
+        let err = "used > && type == 'ext4'".parse::<Expr>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid expression: expected a value, found '&&'\n  used > && type == 'ext4'\n         ^^"
+        );
+    }
+}
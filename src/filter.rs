@@ -0,0 +1,210 @@
+use crate::mount::Mount;
+
+use clap::ValueEnum;
+use glob::{MatchOptions, Pattern};
+use strum_macros::{Display, EnumString};
+
+#[derive(Debug, Clone, PartialEq, Eq, ValueEnum, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DisplayFilter {
+    Minimal,
+    More,
+    All,
+}
+
+impl DisplayFilter {
+    pub const fn from_u8(n: u8) -> Self {
+        match n {
+            0 => Self::Minimal,
+            1 => Self::More,
+            _ => Self::All,
+        }
+    }
+
+    pub fn get_mnt_fsname_filter(&self) -> Vec<&'static str> {
+        match self {
+            Self::Minimal => vec!["/dev*", "storage"],
+            Self::More => vec!["dev", "run", "tmpfs", "/dev*", "storage"],
+            Self::All => vec!["*"],
+        }
+    }
+
+    pub fn matches(&self, mnt: &Mount) -> bool {
+        self.matching_pattern(mnt).is_some()
+    }
+
+    /// The first file system name pattern of this level the mount matches
+    pub fn matching_pattern(&self, mnt: &Mount) -> Option<&'static str> {
+        self.get_mnt_fsname_filter()
+            .into_iter()
+            .find(|fsname| mnt_matches_filter(mnt, fsname))
+    }
+}
+
+/// Glob matching where `*` stays within a path component and `**` spans
+/// several, as in shells.
+const GLOB_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Selection of mounts by their type, mount point and device.
+///
+/// Every non-empty list has to be satisfied, exclusions take precedence
+/// over inclusions.
+#[derive(Debug, Clone, Default)]
+pub struct MountFilter {
+    /// Only mounts of these types
+    pub types: Vec<String>,
+    /// No mounts of these types
+    pub exclude_types: Vec<String>,
+    /// Only mount points matching one of these patterns
+    pub include_mounts: Vec<Pattern>,
+    /// No mount points matching one of these patterns
+    pub exclude_mounts: Vec<Pattern>,
+    /// Only file system names matching one of these patterns
+    pub devices: Vec<Pattern>,
+}
+
+impl MountFilter {
+    /// Whether file systems are explicitly selected by type or device, which
+    /// takes precedence over the display filter
+    pub const fn selects(&self) -> bool {
+        !self.types.is_empty() || !self.devices.is_empty()
+    }
+
+    pub fn matches(&self, mnt: &Mount) -> bool {
+        self.rejection(mnt).is_none()
+    }
+
+    /// The first list the mount does not satisfy
+    pub fn rejection(&self, mnt: &Mount) -> Option<Rejection> {
+        let matches_any = |patterns: &[Pattern], value: &str| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.matches_with(value, GLOB_OPTIONS))
+        };
+        if !self.types.is_empty() && !self.types.contains(&mnt.mnt_type) {
+            Some(Rejection::Type)
+        } else if self.exclude_types.contains(&mnt.mnt_type) {
+            Some(Rejection::ExcludeType)
+        } else if !self.include_mounts.is_empty()
+            && !matches_any(&self.include_mounts, &mnt.mnt_dir)
+        {
+            Some(Rejection::IncludeMount)
+        } else if matches_any(&self.exclude_mounts, &mnt.mnt_dir) {
+            Some(Rejection::ExcludeMount)
+        } else if !self.devices.is_empty() && !matches_any(&self.devices, &mnt.mnt_fsname) {
+            Some(Rejection::Device)
+        } else {
+            None
+        }
+    }
+}
+
+/// The part of a [`MountFilter`] a mount fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    Type,
+    ExcludeType,
+    IncludeMount,
+    ExcludeMount,
+    Device,
+}
+
+#[inline]
+pub fn mnt_matches_filter(mnt: &Mount, filter: &str) -> bool {
+    filter.strip_suffix('*').map_or_else(
+        || mnt.mnt_fsname == filter,
+        |start| mnt.mnt_fsname.starts_with(start),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(fsname: &str, dir: &str, mnt_type: &str) -> Mount {
+        let mut mnt = Mount::named(fsname.into());
+        mnt.mnt_dir = dir.to_string();
+        mnt.mnt_type = mnt_type.to_string();
+        mnt
+    }
+
+    fn patterns(patterns: &[&str]) -> Vec<Pattern> {
+        patterns.iter().map(|p| Pattern::new(p).unwrap()).collect()
+    }
+
+    #[test]
+    fn mount_filter_types() {
+        let filter = MountFilter {
+            types: vec!["ext4".into(), "xfs".into()],
+            exclude_types: vec!["xfs".into()],
+            ..Default::default()
+        };
+        assert!(filter.selects());
+        assert!(filter.matches(&mount("/dev/sda1", "/", "ext4")));
+        assert!(!filter.matches(&mount("/dev/sda2", "/srv", "xfs")));
+        assert!(!filter.matches(&mount("tmpfs", "/tmp", "tmpfs")));
+        assert_eq!(
+            filter.rejection(&mount("/dev/sda2", "/srv", "xfs")),
+            Some(Rejection::ExcludeType)
+        );
+        assert_eq!(
+            filter.rejection(&mount("tmpfs", "/tmp", "tmpfs")),
+            Some(Rejection::Type)
+        );
+    }
+
+    #[test]
+    fn display_filter_matching_pattern() {
+        let tmpfs = mount("tmpfs", "/tmp", "tmpfs");
+        assert_eq!(DisplayFilter::Minimal.matching_pattern(&tmpfs), None);
+        assert_eq!(DisplayFilter::More.matching_pattern(&tmpfs), Some("tmpfs"));
+        assert_eq!(DisplayFilter::All.matching_pattern(&tmpfs), Some("*"));
+        assert_eq!(
+            DisplayFilter::Minimal.matching_pattern(&mount("/dev/sda1", "/", "ext4")),
+            Some("/dev*")
+        );
+    }
+
+    #[test]
+    fn mount_filter_mount_globs() {
+        let filter = MountFilter {
+            exclude_mounts: patterns(&["/var/lib/kubelet/**", "/run/*"]),
+            ..Default::default()
+        };
+        assert!(!filter.selects());
+        assert!(filter.matches(&mount("/dev/sda1", "/var/lib/kubelet", "ext4")));
+        assert!(!filter.matches(&mount(
+            "tmpfs",
+            "/var/lib/kubelet/pods/a/volumes/x",
+            "tmpfs"
+        )));
+        assert!(!filter.matches(&mount("tmpfs", "/run/user", "tmpfs")));
+        assert!(filter.matches(&mount("tmpfs", "/run/user/1000", "tmpfs")));
+
+        let filter = MountFilter {
+            include_mounts: patterns(&["/srv/[ab]*"]),
+            exclude_mounts: patterns(&["/srv/b?"]),
+            ..Default::default()
+        };
+        assert!(filter.matches(&mount("/dev/sda1", "/srv/archive", "ext4")));
+        assert!(!filter.matches(&mount("/dev/sda1", "/srv/b1", "ext4")));
+        assert!(!filter.matches(&mount("/dev/sda1", "/srv/c", "ext4")));
+    }
+
+    #[test]
+    fn mount_filter_devices() {
+        let filter = MountFilter {
+            devices: patterns(&["/dev/mapper/*", "/dev/nvme?n1p*"]),
+            ..Default::default()
+        };
+        assert!(filter.matches(&mount("/dev/mapper/vg0-root", "/", "ext4")));
+        assert!(filter.matches(&mount("/dev/nvme0n1p2", "/boot", "vfat")));
+        assert!(!filter.matches(&mount("/dev/sda1", "/srv", "ext4")));
+    }
+}
@@ -0,0 +1,80 @@
+use crate::args::FleetArgs;
+
+use dfrs::util::calc_total;
+use dfrs::Mount;
+
+/// Whether the mount passes the usage filter of the fleet
+pub fn matches(mnt: &Mount, args: &FleetArgs) -> bool {
+    args.min_used
+        .is_none_or(|min| mnt.used_percentage().is_some_and(|used| used >= min))
+}
+
+/// Merge the mounts of several hosts, following the mounts of each host by
+/// its subtotal.
+pub fn merge_hosts(hosts: Vec<(String, Vec<Mount>)>) -> Vec<Mount> {
+    let mut rows = Vec::new();
+    for (host, mnts) in hosts.into_iter().filter(|(_, mnts)| !mnts.is_empty()) {
+        let mut subtotal = calc_total(&mnts);
+        subtotal.host = Some(host);
+        rows.extend(mnts);
+        rows.push(subtotal);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(host: &str, mnt_type: &str, capacity: u64, free: u64) -> Mount {
+        let mut mnt = Mount::named("/dev/sda1".into());
+        mnt.host = Some(host.to_string());
+        mnt.mnt_type = mnt_type.to_string();
+        mnt.capacity = capacity;
+        mnt.available = free;
+        mnt.used = capacity - free;
+        mnt
+    }
+
+    fn args(min_used: Option<f32>) -> FleetArgs {
+        FleetArgs {
+            min_used,
+            snapshots: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn fleet_filters() {
+        let full = mount("a", "ext4", 100, 10);
+        let empty = mount("a", "ext4", 100, 90);
+        assert!(matches(&full, &args(Some(85.0))));
+        assert!(!matches(&empty, &args(Some(85.0))));
+        assert!(matches(&empty, &args(None)));
+    }
+
+    #[test]
+    fn merge_hosts_subtotals() {
+        let rows = merge_hosts(vec![
+            (
+                "a".to_string(),
+                vec![mount("a", "ext4", 100, 10), mount("a", "ext4", 50, 20)],
+            ),
+            ("b".to_string(), Vec::new()),
+            ("c".to_string(), vec![mount("c", "xfs", 10, 5)]),
+        ]);
+        let summary = rows
+            .iter()
+            .map(|mnt| (mnt.host_formatted(), mnt.fsname(), mnt.used))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("a".to_string(), "/dev/sda1".to_string(), 90),
+                ("a".to_string(), "/dev/sda1".to_string(), 30),
+                ("a".to_string(), "total".to_string(), 120),
+                ("c".to_string(), "/dev/sda1".to_string(), 5),
+                ("c".to_string(), "total".to_string(), 5),
+            ]
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use dfrs::history::Forecast;
+use dfrs::theme::Theme;
+use dfrs::util::{format_percentage, format_signed, NumberFormat};
+
+use colored::*;
+use std::io::{self, Write};
+
+const DAY: f64 = 86400.0;
+
+/// Print the projected dates of the forecasts as a table
+pub fn display_forecasts<W: Write + ?Sized>(
+    w: &mut W,
+    forecasts: &[Forecast],
+    theme: &Theme,
+    delimiter: &NumberFormat,
+) -> io::Result<()> {
+    let color_heading = theme.color_heading.unwrap_or(Color::White);
+    let threshold_heading = format!("{}% on", theme.threshold_usage_high);
+
+    let rows = forecasts
+        .iter()
+        .map(|forecast| {
+            [
+                forecast.dir.clone(),
+                forecast.key.clone(),
+                forecast.samples.to_string(),
+                forecast.rate.map_or_else(
+                    || "-".to_string(),
+                    |rate| format!("{}/d", format_signed(rate * DAY, delimiter)),
+                ),
+                forecast.projection(theme.threshold_usage_high).formatted(),
+                forecast.projection(100.0).formatted(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let headings = [
+        "Mounted on",
+        "File system",
+        "Samples",
+        "Growth",
+        threshold_heading.as_str(),
+        "Full on",
+    ];
+    let widths = headings
+        .iter()
+        .enumerate()
+        .map(|(idx, heading)| {
+            rows.iter()
+                .map(|row| row[idx].len())
+                .chain(std::iter::once(heading.len()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    let mut line = format!(
+        "{:<mounted$} {:<key$} {:>6} ",
+        headings[0].color(color_heading),
+        headings[1].color(color_heading),
+        "Used".color(color_heading),
+        mounted = widths[0],
+        key = widths[1],
+    );
+    for (heading, width) in headings.iter().zip(&widths).skip(2) {
+        line.push_str(&format!(
+            "{:>width$} ",
+            heading.color(color_heading),
+            width = width
+        ));
+    }
+    writeln!(w, "{}", line.trim_end())?;
+
+    for (forecast, row) in forecasts.iter().zip(&rows) {
+        let usage_color = theme.usage_color(forecast.used_percentage());
+        let mut line = format!(
+            "{:<mounted$} {:<key$} {} ",
+            row[0],
+            row[1],
+            format_percentage(forecast.used_percentage()).color(usage_color),
+            mounted = widths[0],
+            key = widths[1],
+        );
+        for (value, width) in row.iter().zip(&widths).skip(2) {
+            line.push_str(&format!("{:>width$} ", value, width = width));
+        }
+        writeln!(w, "{}", line.trim_end())?;
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfrs::util::Scale;
+
+    #[test]
+    fn forecast_table() {
+        colored::control::set_override(false);
+        let forecast = Forecast {
+            key: "uuid:1234".to_string(),
+            dir: "/".to_string(),
+            samples: 3,
+            timestamp: 0,
+            used: 500,
+            available: 500,
+            rate: Some(250.0 / DAY),
+        };
+        let mut out = Vec::new();
+        display_forecasts(
+            &mut out,
+            &[forecast],
+            &Theme::new(),
+            &NumberFormat::new(Scale::Raw),
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "Mounted on File system   Used Samples Growth     75% on    Full on"
+        );
+        assert_eq!(
+            lines[1],
+            "/          uuid:1234    50.0%       3 +250/d 1970-01-02 1970-01-03"
+        );
+    }
+}
@@ -0,0 +1,507 @@
+use crate::errors::{Error, Result};
+use crate::mount::{escape_octal, unescape_octal, Mount};
+use crate::util::{format_date, write_atomic};
+
+use log::warn;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "# dfrs history v1";
+
+/// Location of the history store, `$XDG_STATE_HOME/dfrs/history` with a
+/// fallback to `~/.local/state/dfrs/history`.
+pub fn default_path() -> Option<PathBuf> {
+    let absolute = |var: &str| {
+        env::var_os(var)
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+    };
+    absolute("XDG_STATE_HOME")
+        .or_else(|| absolute("HOME").map(|home| home.join(".local/state")))
+        .map(|state| state.join("dfrs").join("history"))
+}
+
+/// Resolves the identity file systems are recorded under, which stays the
+/// same when they are mounted elsewhere or the device is renumbered.
+#[derive(Debug, Default)]
+pub struct MountKeys {
+    uuids: HashMap<PathBuf, String>,
+}
+
+impl MountKeys {
+    pub fn new() -> Self {
+        Self::from_dir("/dev/disk/by-uuid")
+    }
+
+    /// Read the UUIDs from a directory of symlinks named after them
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Self {
+        let uuids = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let device = entry.path().canonicalize().ok()?;
+                let uuid = entry.file_name().into_string().ok()?;
+                Some((device, uuid))
+            })
+            .collect();
+        Self { uuids }
+    }
+
+    /// `uuid:<UUID>` if known, `dev:<device>` for block devices and
+    /// `dir:<mount point>` for everything else.
+    pub fn key(&self, mnt: &Mount) -> String {
+        if !mnt.mnt_fsname.starts_with('/') {
+            return format!("dir:{}", mnt.mnt_dir);
+        }
+        Path::new(&mnt.mnt_fsname)
+            .canonicalize()
+            .ok()
+            .and_then(|device| self.uuids.get(&device))
+            .map_or_else(
+                || format!("dev:{}", mnt.mnt_fsname),
+                |uuid| format!("uuid:{}", uuid),
+            )
+    }
+}
+
+/// Usage of a single file system at a point in time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub timestamp: u64,
+    pub key: String,
+    pub dir: String,
+    pub capacity: u64,
+    /// Space in use, not counting the reserved space
+    pub used: u64,
+    /// Space available to unprivileged users
+    pub available: u64,
+    /// Free space only available to the superuser
+    pub reserved: u64,
+    pub files: u64,
+    pub files_free: u64,
+}
+
+impl Entry {
+    /// Take the byte and inode usage from the statfs numbers of the mount
+    pub fn from_mount(mnt: &Mount, key: String, timestamp: u64) -> Option<Self> {
+        let stat = mnt.statfs.filter(|stat| stat.blocks > 0)?;
+        let free = stat.blocks_free.min(stat.blocks);
+        let available = stat.blocks_available.min(free);
+        Some(Self {
+            timestamp,
+            key,
+            dir: mnt.mnt_dir.clone(),
+            capacity: stat.blocks * stat.block_size,
+            used: (stat.blocks - free) * stat.block_size,
+            available: available * stat.block_size,
+            reserved: (free - available) * stat.block_size,
+            files: stat.files,
+            files_free: stat.files_free,
+        })
+    }
+
+    fn parse(line: &str) -> Result<Self> {
+        let mut fields = line.split_ascii_whitespace();
+        let mut next = |name: &'static str| fields.next().ok_or(Error::MissingField(name));
+        let number = |name: &'static str, value: &str| {
+            value.parse::<u64>().map_err(|err| Error::InvalidField {
+                field: name,
+                value: value.to_string(),
+                source: Some(err),
+            })
+        };
+        Ok(Self {
+            timestamp: number("timestamp", next("timestamp")?)?,
+            key: unescape_octal(next("key")?),
+            capacity: number("capacity", next("capacity")?)?,
+            used: number("used", next("used")?)?,
+            available: number("available", next("available")?)?,
+            reserved: number("reserved", next("reserved")?)?,
+            files: number("files", next("files")?)?,
+            files_free: number("files_free", next("files_free")?)?,
+            dir: unescape_octal(next("dir")?),
+        })
+    }
+
+    fn write<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(
+            w,
+            "{} {} {} {} {} {} {} {} {}",
+            self.timestamp,
+            escape_octal(&self.key),
+            self.capacity,
+            self.used,
+            self.available,
+            self.reserved,
+            self.files,
+            self.files_free,
+            escape_octal(&self.dir),
+        )
+    }
+}
+
+/// Recorded usage samples, stored as one line per file system and sample.
+#[derive(Debug, Default)]
+pub struct History {
+    pub entries: Vec<Entry>,
+}
+
+impl History {
+    /// Load the history, a missing file is an empty history
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(source) => Err(Error::ReadHistory {
+                path: path.to_path_buf(),
+                source,
+            }),
+        }
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let entries = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+            .filter_map(|(idx, line)| match Entry::parse(line) {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    warn!("Skipping malformed history line {}: {}", idx + 1, err);
+                    None
+                }
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Atomically replace the history file, creating its directory
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let error = |source| Error::WriteHistory {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(error)?;
+        }
+        write_atomic(path, |w| {
+            writeln!(w, "{}", HEADER)?;
+            self.entries.iter().try_for_each(|entry| entry.write(w))
+        })
+        .map_err(error)
+    }
+
+    /// Append a sample of every mount, file systems mounted more than once
+    /// are only recorded once.
+    pub fn record(&mut self, mnts: &[Mount], keys: &MountKeys, timestamp: u64) -> usize {
+        let mut seen = HashSet::new();
+        let before = self.entries.len();
+        for mnt in mnts {
+            let key = keys.key(mnt);
+            if seen.contains(&key) {
+                continue;
+            }
+            if let Some(entry) = Entry::from_mount(mnt, key.clone(), timestamp) {
+                seen.insert(key);
+                self.entries.push(entry);
+            }
+        }
+        self.entries.len() - before
+    }
+
+    /// Drop the samples taken before the given time
+    pub fn prune(&mut self, before: u64) {
+        self.entries.retain(|entry| entry.timestamp >= before);
+    }
+
+    /// Fit the growth of every recorded file system, ordered by mount point
+    pub fn forecast(&self, inodes: bool) -> Vec<Forecast> {
+        let mut series: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+        for entry in &self.entries {
+            series.entry(&entry.key).or_default().push(entry);
+        }
+
+        let mut forecasts = series
+            .into_iter()
+            .filter_map(|(key, mut entries)| {
+                entries.sort_by_key(|entry| entry.timestamp);
+                let usage = |entry: &Entry| {
+                    if inodes {
+                        (
+                            entry.files.saturating_sub(entry.files_free),
+                            entry.files_free,
+                        )
+                    } else {
+                        (entry.used, entry.available)
+                    }
+                };
+                let latest = entries.last()?;
+                let (used, available) = usage(latest);
+                let points = entries
+                    .iter()
+                    .map(|entry| (entry.timestamp as f64, usage(entry).0 as f64))
+                    .collect::<Vec<_>>();
+                Some(Forecast {
+                    key: key.to_string(),
+                    dir: latest.dir.clone(),
+                    samples: entries.len(),
+                    timestamp: latest.timestamp,
+                    used,
+                    available,
+                    rate: least_squares_slope(&points),
+                })
+            })
+            .collect::<Vec<_>>();
+        forecasts.sort_by(|a, b| a.dir.cmp(&b.dir).then_with(|| a.key.cmp(&b.key)));
+        forecasts
+    }
+}
+
+/// Slope of the least squares line through the points, None without a
+/// time span to fit over.
+fn least_squares_slope(points: &[(f64, f64)]) -> Option<f64> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let (sxx, sxy) = points.iter().fold((0.0, 0.0), |(sxx, sxy), (x, y)| {
+        let dx = x - mean_x;
+        (dx.mul_add(dx, sxx), dx.mul_add(y - mean_y, sxy))
+    });
+    (sxx > 0.0).then(|| sxy / sxx)
+}
+
+/// Projected growth of a file system from its recorded history
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forecast {
+    pub key: String,
+    pub dir: String,
+    pub samples: usize,
+    /// Time of the latest sample
+    pub timestamp: u64,
+    /// Space (or inodes) in use, not counting the reserved space
+    pub used: u64,
+    /// Space (or inodes) available to unprivileged users
+    pub available: u64,
+    /// Growth in bytes (or inodes) per second
+    pub rate: Option<f64>,
+}
+
+impl Forecast {
+    /// Share of the used and available space in use, like GNU df
+    pub fn used_percentage(&self) -> Option<f32> {
+        match self.used + self.available {
+            0 => None,
+            total => Some(self.used as f32 * 100.0 / total as f32),
+        }
+    }
+
+    /// When the usage reaches the given percentage of the used and
+    /// available space
+    pub fn projection(&self, percentage: f32) -> Projection {
+        let total = (self.used + self.available) as f64;
+        let target = total * f64::from(percentage) / 100.0;
+        let remaining = target - self.used as f64;
+        if remaining <= 0.0 {
+            return Projection::Reached;
+        }
+        match self.rate {
+            None => Projection::Unknown,
+            Some(rate) if rate > 0.0 => {
+                Projection::At(self.timestamp + (remaining / rate).ceil() as u64)
+            }
+            Some(_) => Projection::Never,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+    /// The usage is already at or above the target
+    Reached,
+    /// Seconds since the Unix epoch the target is reached at
+    At(u64),
+    /// The usage is not growing
+    Never,
+    /// Not enough samples to tell
+    Unknown,
+}
+
+impl Projection {
+    pub fn formatted(&self) -> String {
+        match self {
+            Self::Reached => "reached".to_string(),
+            Self::At(timestamp) => format_date(*timestamp),
+            Self::Never => "never".to_string(),
+            Self::Unknown => "-".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mount::StatfsInfo;
+
+    const DAY: u64 = 86400;
+
+    fn mount(fsname: &str, dir: &str, blocks_available: u64) -> Mount {
+        let mut mnt = Mount::named(fsname.to_string());
+        mnt.mnt_dir = dir.to_string();
+        mnt.statfs = Some(StatfsInfo {
+            block_size: 1024,
+            blocks: 1000,
+            blocks_free: blocks_available,
+            blocks_available,
+            files: 100,
+            files_free: 40,
+        });
+        mnt
+    }
+
+    #[test]
+    fn entry_roundtrip() {
+        let entry = Entry::from_mount(
+            &mount("/dev/sda1", "/mnt/my disk", 250),
+            "dev:/dev/sda1".to_string(),
+            1_700_000_000,
+        )
+        .unwrap();
+        assert_eq!(entry.capacity, 1_024_000);
+        assert_eq!(entry.used, 768_000);
+
+        let mut line = Vec::new();
+        entry.write(&mut line).unwrap();
+        let line = String::from_utf8(line).unwrap();
+        assert_eq!(
+            line,
+            "1700000000 dev:/dev/sda1 1024000 768000 256000 0 100 40 /mnt/my\\040disk\n"
+        );
+        assert_eq!(Entry::parse(&line).unwrap(), entry);
+    }
+
+    #[test]
+    fn reserved_space_is_not_used() {
+        let mut mnt = mount("/dev/sda1", "/", 100);
+        mnt.statfs.as_mut().unwrap().blocks_free = 200;
+        let entry = Entry::from_mount(&mnt, "dev:/dev/sda1".to_string(), 0).unwrap();
+        assert_eq!(
+            (entry.used, entry.available, entry.reserved),
+            (800 * 1024, 100 * 1024, 100 * 1024)
+        );
+
+        let mut history = History::default();
+        history.entries.push(entry);
+        let forecast = &history.forecast(false)[0];
+        assert_eq!(forecast.used_percentage(), Some(800.0 * 100.0 / 900.0));
+        assert_eq!(forecast.projection(80.0), Projection::Reached);
+    }
+
+    #[test]
+    fn parse_skips_malformed_lines() {
+        let history = History::parse(&format!(
+            "{}\n\n10 dir:/ 100 50 50 0 0 0 /\n10 dir:/ 100\n20 dir:/ x 50 50 0 0 0 /\n",
+            HEADER
+        ));
+        assert_eq!(history.entries.len(), 1);
+        assert_eq!(history.entries[0].dir, "/");
+    }
+
+    #[test]
+    fn mount_keys() {
+        let keys = MountKeys::default();
+        assert_eq!(keys.key(&mount("tmpfs", "/tmp", 0)), "dir:/tmp");
+        assert_eq!(keys.key(&mount("/dev/sda1", "/", 0)), "dev:/dev/sda1");
+
+        let dir = env::temp_dir().join(format!("dfrs-keys-{}", std::process::id()));
+        fs::create_dir_all(dir.join("by-uuid")).unwrap();
+        fs::write(dir.join("sda1"), "").unwrap();
+        std::os::unix::fs::symlink("../sda1", dir.join("by-uuid/1234-abcd")).unwrap();
+        let keys = MountKeys::from_dir(dir.join("by-uuid"));
+        let device = dir.join("sda1").to_string_lossy().into_owned();
+        assert_eq!(keys.key(&mount(&device, "/", 0)), "uuid:1234-abcd");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn record_once_per_file_system_and_prune() {
+        let mut history = History::default();
+        let keys = MountKeys::default();
+        let mut pseudo = mount("proc", "/proc", 0);
+        pseudo.statfs = None;
+        let mnts = [
+            mount("/dev/sda1", "/", 500),
+            mount("/dev/sda1", "/srv", 500),
+            pseudo,
+        ];
+        assert_eq!(history.record(&mnts, &keys, 10), 1);
+        assert_eq!(history.record(&mnts, &keys, 20), 1);
+        history.prune(15);
+        assert_eq!(history.entries.len(), 1);
+        assert_eq!(history.entries[0].timestamp, 20);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = env::temp_dir().join(format!("dfrs-history-{}", std::process::id()));
+        let path = dir.join("state/history");
+        assert!(History::load(&path).unwrap().entries.is_empty());
+
+        let mut history = History::default();
+        history.record(&[mount("/dev/sda1", "/", 500)], &MountKeys::default(), 10);
+        history.save(&path).unwrap();
+        assert_eq!(History::load(&path).unwrap().entries, history.entries);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn forecast_linear_growth() {
+        let keys = MountKeys::default();
+        let mut history = History::default();
+        // 10% of the capacity more every day, starting at 40% used
+        for day in 0..4 {
+            let available = 600 - 100 * day;
+            history.record(&[mount("/dev/sda1", "/", available)], &keys, day * DAY);
+        }
+        let forecasts = history.forecast(false);
+        assert_eq!(forecasts.len(), 1);
+
+        let forecast = &forecasts[0];
+        assert_eq!(forecast.samples, 4);
+        assert_eq!(forecast.used_percentage(), Some(70.0));
+        assert_eq!(
+            forecast.rate.unwrap().round(),
+            (102_400.0 / DAY as f64).round()
+        );
+        assert_eq!(forecast.projection(70.0), Projection::Reached);
+        assert_eq!(forecast.projection(80.0), Projection::At(4 * DAY));
+        assert_eq!(forecast.projection(100.0), Projection::At(6 * DAY));
+    }
+
+    #[test]
+    fn forecast_shrinking_and_single_sample() {
+        let keys = MountKeys::default();
+        let mut history = History::default();
+        history.record(&[mount("/dev/sda1", "/", 500)], &keys, 0);
+        history.record(&[mount("/dev/sda2", "/srv", 500)], &keys, 0);
+        history.record(&[mount("/dev/sda2", "/srv", 600)], &keys, DAY);
+
+        let forecasts = history.forecast(false);
+        assert_eq!(forecasts[0].dir, "/");
+        assert_eq!(forecasts[0].projection(100.0), Projection::Unknown);
+        assert_eq!(forecasts[1].dir, "/srv");
+        assert_eq!(forecasts[1].projection(100.0), Projection::Never);
+        assert_eq!(history.forecast(true)[0].used_percentage(), Some(60.0));
+    }
+
+    #[test]
+    fn projection_formatted() {
+        assert_eq!(Projection::At(0).formatted(), "1970-01-01");
+        assert_eq!(Projection::Reached.formatted(), "reached");
+    }
+}
@@ -0,0 +1,216 @@
+//! Fitting the table into the width of the terminal.
+//!
+//! The bars shrink first, then the file system and mount point columns are
+//! truncated in the middle, and only then whole columns are dropped.
+
+use dfrs::column::ColumnType;
+
+use std::io::{stdout, IsTerminal};
+use terminal_size::{terminal_size, Width};
+
+/// Narrowest a bar shrinks to
+const MIN_BAR_WIDTH: usize = 5;
+/// Narrowest the file system and mount point columns are truncated to,
+/// enough for their headings
+const MIN_NAME_WIDTH: usize = 10;
+
+/// Columns in the order they are dropped, columns not listed are kept
+const DROP_ORDER: [ColumnType; 18] = [
+    ColumnType::Reserved,
+    ColumnType::Free,
+    ColumnType::AvailablePercentage,
+    ColumnType::Delta,
+    ColumnType::Rate,
+    ColumnType::Inodes,
+    ColumnType::InodesFree,
+    ColumnType::InodesUsed,
+    ColumnType::Status,
+    ColumnType::Type,
+    ColumnType::Capacity,
+    ColumnType::Used,
+    ColumnType::InodeBar,
+    ColumnType::Bar,
+    ColumnType::TimeToFull,
+    ColumnType::Host,
+    ColumnType::Available,
+    ColumnType::InodesUsedPercentage,
+];
+
+/// The columns of the table and the widths of those that adapt to the
+/// available space.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub columns: Vec<ColumnType>,
+    pub bar_width: usize,
+    pub fsname_width: usize,
+    pub mounted_width: usize,
+    /// Characters the bars take on top of their width, like brackets
+    pub bar_decoration: usize,
+}
+
+impl Layout {
+    /// Width of the table, with a space between the columns
+    pub fn width<F>(&self, fixed_width: F) -> usize
+    where
+        F: Fn(&ColumnType) -> usize,
+    {
+        self.columns
+            .iter()
+            .map(|column| match column {
+                ColumnType::Filesystem => self.fsname_width,
+                ColumnType::MountedOn => self.mounted_width,
+                ColumnType::Bar | ColumnType::InodeBar => self.bar_width + self.bar_decoration,
+                column => fixed_width(column),
+            })
+            .map(|width| width + 1)
+            .sum::<usize>()
+            .saturating_sub(1)
+    }
+
+    /// Shrink the table to at most `max_width` characters if possible.
+    ///
+    /// Columns are only dropped if shrinking the bars and truncating the
+    /// names is not enough; the names then get back what the dropped
+    /// columns freed.
+    pub fn fit<F>(&mut self, max_width: usize, fixed_width: F)
+    where
+        F: Fn(&ColumnType) -> usize,
+    {
+        let natural = self.clone();
+        let excess = |layout: &Self| layout.width(&fixed_width).saturating_sub(max_width);
+
+        let bars = self
+            .columns
+            .iter()
+            .filter(|column| matches!(column, ColumnType::Bar | ColumnType::InodeBar))
+            .count();
+        if bars > 0 {
+            let shrinkable = self.bar_width.saturating_sub(MIN_BAR_WIDTH);
+            self.bar_width -= excess(self).div_ceil(bars).min(shrinkable);
+        }
+
+        while excess(self) > 0 && self.shrink_names() {}
+
+        for column in &DROP_ORDER {
+            if excess(self) == 0 {
+                break;
+            }
+            self.columns.retain(|c| c != column);
+        }
+
+        let mut spare = max_width.saturating_sub(self.width(&fixed_width));
+        for (width, natural) in [
+            (&mut self.mounted_width, natural.mounted_width),
+            (&mut self.fsname_width, natural.fsname_width),
+        ] {
+            let grow = natural.saturating_sub(*width).min(spare);
+            *width += grow;
+            spare -= grow;
+        }
+    }
+
+    /// Take a character from the wider of the name columns, false if both
+    /// are as narrow as they get
+    fn shrink_names(&mut self) -> bool {
+        let fsname = self.columns.contains(&ColumnType::Filesystem);
+        let mounted = self.columns.contains(&ColumnType::MountedOn);
+        let width = match (fsname, mounted) {
+            (true, true) if self.mounted_width >= self.fsname_width => &mut self.mounted_width,
+            (true, _) => &mut self.fsname_width,
+            (false, true) => &mut self.mounted_width,
+            (false, false) => return false,
+        };
+        if *width <= MIN_NAME_WIDTH {
+            return false;
+        }
+        *width -= 1;
+        true
+    }
+}
+
+/// Width the table has to fit into: the one given, none for `--no-truncate`,
+/// or that of the terminal when writing to one
+pub fn max_width(width: Option<usize>, no_truncate: bool, to_stdout: bool) -> Option<usize> {
+    if no_truncate {
+        None
+    } else if width.is_some() {
+        width
+    } else if to_stdout && stdout().is_terminal() {
+        terminal_size().map(|(Width(width), _)| usize::from(width))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout() -> Layout {
+        Layout {
+            columns: vec![
+                ColumnType::Filesystem,
+                ColumnType::Type,
+                ColumnType::Bar,
+                ColumnType::UsedPercentage,
+                ColumnType::Available,
+                ColumnType::MountedOn,
+            ],
+            bar_width: 20,
+            fsname_width: 30,
+            mounted_width: 40,
+            bar_decoration: 0,
+        }
+    }
+
+    fn fixed(column: &ColumnType) -> usize {
+        match column {
+            ColumnType::Type => 5,
+            _ => 6,
+        }
+    }
+
+    #[test]
+    fn fits_without_changes() {
+        let mut fitted = layout();
+        let width = fitted.width(fixed);
+        assert_eq!(width, 30 + 5 + 20 + 6 + 6 + 40 + 5);
+        fitted.fit(width, fixed);
+        assert_eq!(fitted, layout());
+    }
+
+    #[test]
+    fn shrinks_bar_first() {
+        let mut fitted = layout();
+        fitted.fit(100, fixed);
+        assert_eq!(fitted.bar_width, 8);
+        assert_eq!((fitted.fsname_width, fitted.mounted_width), (30, 40));
+    }
+
+    #[test]
+    fn truncates_the_wider_name_next() {
+        let mut fitted = layout();
+        fitted.fit(80, fixed);
+        assert_eq!(fitted.bar_width, MIN_BAR_WIDTH);
+        assert_eq!((fitted.fsname_width, fitted.mounted_width), (27, 26));
+        assert_eq!(fitted.width(fixed), 80);
+        assert_eq!(fitted.columns, layout().columns);
+    }
+
+    #[test]
+    fn drops_columns_last() {
+        let mut fitted = layout();
+        fitted.fit(40, fixed);
+        assert_eq!(
+            fitted.columns,
+            [
+                ColumnType::Filesystem,
+                ColumnType::UsedPercentage,
+                ColumnType::Available,
+                ColumnType::MountedOn
+            ]
+        );
+        assert_eq!((fitted.fsname_width, fitted.mounted_width), (10, 15));
+        assert_eq!(fitted.width(fixed), 40);
+    }
+}
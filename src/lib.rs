@@ -0,0 +1,26 @@
+//! Display file system space usage using graphs and colors
+//!
+//! This crate provides the mount table parsers, filters and usage queries
+//! behind the `dfrs` command line tool.
+#![deny(clippy::nursery, clippy::cargo)]
+
+pub mod column;
+#[cfg(feature = "serde")]
+pub mod config;
+pub mod diff;
+pub mod errors;
+pub mod expr;
+pub mod filter;
+pub mod history;
+pub mod mount;
+pub mod query;
+#[cfg(feature = "serde")]
+pub mod snapshot;
+pub mod statfs;
+pub mod theme;
+pub mod util;
+
+pub use errors::{Error, Result};
+pub use mount::Mount;
+pub use query::MountQuery;
+pub use util::try_print;
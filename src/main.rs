@@ -2,53 +2,61 @@
 use args::*;
 mod args;
 
-mod errors;
-use errors::*;
-
-mod theme;
-use theme::Theme;
-
-mod mount;
-use mount::*;
-
-mod util;
-use util::bar;
-use util::try_print;
-
-use std::fs::File;
-use std::path::Path;
-use std::path::PathBuf;
-
-use nix::sys::statfs;
+mod check;
+mod compare;
+mod df;
+mod explain;
+mod fleet;
+mod forecast;
+mod layout;
+mod output;
+mod watch;
+
+use layout::Layout;
+
+use dfrs::column::ColumnType;
+use dfrs::config::Config;
+use dfrs::diff;
+use dfrs::filter::{DisplayFilter, MountFilter};
+use dfrs::history::{self, History, MountKeys};
+use dfrs::query::MountQuery;
+use dfrs::snapshot::Snapshot;
+use dfrs::theme::Theme;
+use dfrs::util::{self, bar, format_percentage, truncate_middle, NumberFormat, Scale};
+use dfrs::Mount;
 
 use env_logger::Env;
 
-use crate::mount::Mount;
-use crate::util::format_percentage;
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
 use colored::*;
-use std::io::{stdout, Write};
+use log::debug;
+use std::io::{self, stdout, Write};
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[inline]
 fn column_width<F>(mnt: &[Mount], f: F, heading: &str) -> usize
 where
-    F: Fn(&Mount) -> usize,
+    F: Fn(&Mount) -> String,
 {
     mnt.iter()
-        .map(f)
-        .chain(std::iter::once(heading.len()))
+        .map(|m| f(m).chars().count())
+        .chain(std::iter::once(heading.chars().count()))
         .max()
         .unwrap()
 }
 
-fn display_mounts(
+fn display_mounts<W: Write + ?Sized>(
+    w: &mut W,
     mnts: &[Mount],
     theme: &Theme,
     delimiter: &NumberFormat,
     inodes_mode: bool,
     no_aliases: bool,
-) {
+    max_width: Option<usize>,
+) -> io::Result<()> {
     let color_heading = theme.color_heading.unwrap_or(Color::White);
 
     let fsname_func = if no_aliases {
@@ -57,36 +65,124 @@ fn display_mounts(
         Mount::fsname_aliased
     };
 
-    let fsname_width = column_width(
-        mnts,
-        |m| fsname_func(m).len(),
-        ColumnType::Filesystem.label(inodes_mode),
-    );
+    let fsname_width = column_width(mnts, fsname_func, ColumnType::Filesystem.label(inodes_mode));
     let type_width = column_width(
         mnts,
-        |m| m.mnt_type.len(),
+        |m| m.mnt_type.clone(),
         ColumnType::Type.label(inodes_mode),
     );
     let available_width = column_width(
         mnts,
-        |m| m.free_formatted(delimiter).len(),
+        |m| m.available_formatted(delimiter),
         ColumnType::Available.label(inodes_mode),
     );
     let used_width = column_width(
         mnts,
-        |m| m.used_formatted(delimiter).len(),
+        |m| m.used_formatted(delimiter),
         ColumnType::Used.label(inodes_mode),
     );
+    let reserved_width = column_width(
+        mnts,
+        |m| m.reserved_formatted(delimiter),
+        ColumnType::Reserved.label(inodes_mode),
+    );
+    let free_width = column_width(
+        mnts,
+        |m| m.free_formatted(delimiter),
+        ColumnType::Free.label(inodes_mode),
+    );
     let capacity_width = column_width(
         mnts,
-        |m| m.capacity_formatted(delimiter).len(),
+        |m| m.capacity_formatted(delimiter),
         ColumnType::Capacity.label(inodes_mode),
     );
     let mounted_width = column_width(
         mnts,
-        |m| m.mnt_dir.len(),
+        |m| m.dirs_formatted(),
         ColumnType::MountedOn.label(inodes_mode),
     );
+    let delta_width = column_width(
+        mnts,
+        |m| m.delta_formatted(delimiter),
+        ColumnType::Delta.label(inodes_mode),
+    );
+    let rate_width = column_width(
+        mnts,
+        |m| m.rate_formatted(delimiter),
+        ColumnType::Rate.label(inodes_mode),
+    );
+    let time_to_full_width = column_width(
+        mnts,
+        |m| m.time_to_full_formatted(),
+        ColumnType::TimeToFull.label(inodes_mode),
+    );
+    let host_width = column_width(
+        mnts,
+        |m| m.host_formatted(),
+        ColumnType::Host.label(inodes_mode),
+    );
+    let inodes_width = column_width(
+        mnts,
+        |m| m.inodes_formatted(delimiter),
+        ColumnType::Inodes.label(inodes_mode),
+    );
+    let inodes_used_width = column_width(
+        mnts,
+        |m| m.inodes_used_formatted(delimiter),
+        ColumnType::InodesUsed.label(inodes_mode),
+    );
+    let inodes_free_width = column_width(
+        mnts,
+        |m| m.inodes_free_formatted(delimiter),
+        ColumnType::InodesFree.label(inodes_mode),
+    );
+    let status_width = column_width(
+        mnts,
+        |m| m.status_formatted(),
+        ColumnType::Status.label(inodes_mode),
+    );
+
+    let fixed_width = |column: &ColumnType| -> usize {
+        match column {
+            ColumnType::Filesystem => fsname_width,
+            ColumnType::Type => type_width,
+            ColumnType::Bar | ColumnType::InodeBar => theme.bar_width,
+            ColumnType::Used => used_width,
+            ColumnType::UsedPercentage
+            | ColumnType::AvailablePercentage
+            | ColumnType::InodesUsedPercentage => 6,
+            ColumnType::Available => available_width,
+            ColumnType::Reserved => reserved_width,
+            ColumnType::Free => free_width,
+            ColumnType::Capacity => capacity_width,
+            ColumnType::MountedOn => mounted_width,
+            ColumnType::Delta => delta_width,
+            ColumnType::Rate => rate_width,
+            ColumnType::TimeToFull => time_to_full_width,
+            ColumnType::Host => host_width,
+            ColumnType::Status => status_width,
+            ColumnType::Inodes => inodes_width,
+            ColumnType::InodesUsed => inodes_used_width,
+            ColumnType::InodesFree => inodes_free_width,
+        }
+    };
+    let mut layout = Layout {
+        columns: theme.columns.clone(),
+        bar_width: theme.bar_width,
+        fsname_width,
+        mounted_width,
+        bar_decoration: theme.char_bar_open.chars().count() + theme.char_bar_close.chars().count(),
+    };
+    if let Some(max_width) = max_width {
+        layout.fit(max_width, fixed_width);
+    }
+    let Layout {
+        columns,
+        bar_width,
+        fsname_width,
+        mounted_width,
+        ..
+    } = layout;
 
     let print_heading_left_func = |column: &ColumnType, width: usize| -> String {
         format!(
@@ -105,7 +201,7 @@ fn display_mounts(
     };
 
     let mut line = String::new();
-    for column in &theme.columns {
+    for column in &columns {
         match column {
             ColumnType::Filesystem => {
                 line.push_str(print_heading_left_func(column, fsname_width).as_str());
@@ -114,7 +210,7 @@ fn display_mounts(
                 line.push_str(print_heading_left_func(column, type_width).as_str());
             }
             ColumnType::Bar => {
-                line.push_str(print_heading_left_func(column, theme.bar_width).as_str());
+                line.push_str(print_heading_left_func(column, bar_width).as_str());
             }
             ColumnType::Used => {
                 line.push_str(print_heading_right_func(column, used_width).as_str());
@@ -128,30 +224,82 @@ fn display_mounts(
             ColumnType::AvailablePercentage => {
                 line.push_str(print_heading_right_func(column, 6).as_str());
             }
+            ColumnType::Reserved => {
+                line.push_str(print_heading_right_func(column, reserved_width).as_str());
+            }
+            ColumnType::Free => {
+                line.push_str(print_heading_right_func(column, free_width).as_str());
+            }
             ColumnType::Capacity => {
                 line.push_str(print_heading_right_func(column, capacity_width).as_str());
             }
             ColumnType::MountedOn => {
                 line.push_str(print_heading_left_func(column, mounted_width).as_str());
             }
+            ColumnType::Delta => {
+                line.push_str(print_heading_right_func(column, delta_width).as_str());
+            }
+            ColumnType::Rate => {
+                line.push_str(print_heading_right_func(column, rate_width).as_str());
+            }
+            ColumnType::TimeToFull => {
+                line.push_str(print_heading_right_func(column, time_to_full_width).as_str());
+            }
+            ColumnType::Host => {
+                line.push_str(print_heading_left_func(column, host_width).as_str());
+            }
+            ColumnType::Status => {
+                line.push_str(print_heading_left_func(column, status_width).as_str());
+            }
+            ColumnType::Inodes => {
+                line.push_str(print_heading_right_func(column, inodes_width).as_str());
+            }
+            ColumnType::InodesUsed => {
+                line.push_str(print_heading_right_func(column, inodes_used_width).as_str());
+            }
+            ColumnType::InodesFree => {
+                line.push_str(print_heading_right_func(column, inodes_free_width).as_str());
+            }
+            ColumnType::InodesUsedPercentage => {
+                line.push_str(print_heading_right_func(column, 6).as_str());
+            }
+            ColumnType::InodeBar => {
+                line.push_str(print_heading_left_func(column, bar_width).as_str());
+            }
         }
     }
-    if try_println!("{}", line.trim_end()).is_err() {
-        return;
-    }
+    writeln!(w, "{}", line.trim_end())?;
 
     for mnt in mnts {
         let usage_color = mnt.usage_color(theme);
 
         let used_percentage = format_percentage(mnt.used_percentage()).color(usage_color);
-        let available_percentage = format_percentage(mnt.free_percentage()).color(usage_color);
+        let available_percentage = format_percentage(mnt.available_percentage()).color(usage_color);
+        let inode_color = if theme.color_by_worse {
+            usage_color
+        } else {
+            theme.usage_color(mnt.inodes_used_percentage())
+        };
+        let changed = mnt.trend.is_some_and(|trend| trend.delta != 0);
+        let highlight = |s: String| -> ColoredString {
+            if changed {
+                s.bold()
+            } else {
+                s.normal()
+            }
+        };
 
         line.clear();
-        for column in &theme.columns {
+        for column in &columns {
             match column {
                 ColumnType::Filesystem => {
                     line.push_str(
-                        format!("{:<width$} ", fsname_func(mnt), width = fsname_width).as_str(),
+                        format!(
+                            "{:<width$} ",
+                            highlight(truncate_middle(&fsname_func(mnt), fsname_width)),
+                            width = fsname_width
+                        )
+                        .as_str(),
                     );
                 }
                 ColumnType::Type => {
@@ -163,8 +311,13 @@ fn display_mounts(
                     line.push_str(
                         format!(
                             "{:<width$} ",
-                            bar(theme.bar_width, mnt.used_percentage(), theme),
-                            width = theme.bar_width
+                            bar(
+                                bar_width,
+                                mnt.used_percentage(),
+                                mnt.reserved_percentage().unwrap_or(0.0),
+                                theme
+                            ),
+                            width = bar_width
                         )
                         .as_str(),
                     );
@@ -186,7 +339,7 @@ fn display_mounts(
                     line.push_str(
                         format!(
                             "{:>width$} ",
-                            mnt.free_formatted(delimiter).color(usage_color),
+                            mnt.available_formatted(delimiter).color(usage_color),
                             width = available_width
                         )
                         .as_str(),
@@ -195,6 +348,26 @@ fn display_mounts(
                 ColumnType::AvailablePercentage => {
                     line.push_str(format!("{} ", available_percentage).as_str());
                 }
+                ColumnType::Reserved => {
+                    line.push_str(
+                        format!(
+                            "{:>width$} ",
+                            mnt.reserved_formatted(delimiter).color(usage_color),
+                            width = reserved_width
+                        )
+                        .as_str(),
+                    );
+                }
+                ColumnType::Free => {
+                    line.push_str(
+                        format!(
+                            "{:>width$} ",
+                            mnt.free_formatted(delimiter).color(usage_color),
+                            width = free_width
+                        )
+                        .as_str(),
+                    );
+                }
                 ColumnType::Capacity => {
                     line.push_str(
                         format!(
@@ -207,20 +380,113 @@ fn display_mounts(
                 }
                 ColumnType::MountedOn => {
                     line.push_str(
-                        format!("{:<width$} ", mnt.mnt_dir, width = mounted_width).as_str(),
+                        format!(
+                            "{:<width$} ",
+                            highlight(truncate_middle(&mnt.dirs_formatted(), mounted_width)),
+                            width = mounted_width
+                        )
+                        .as_str(),
+                    );
+                }
+                ColumnType::Delta => {
+                    line.push_str(
+                        format!(
+                            "{:>width$} ",
+                            highlight(mnt.delta_formatted(delimiter)),
+                            width = delta_width
+                        )
+                        .as_str(),
+                    );
+                }
+                ColumnType::Rate => {
+                    line.push_str(
+                        format!(
+                            "{:>width$} ",
+                            highlight(mnt.rate_formatted(delimiter)),
+                            width = rate_width
+                        )
+                        .as_str(),
+                    );
+                }
+                ColumnType::TimeToFull => {
+                    line.push_str(
+                        format!(
+                            "{:>width$} ",
+                            mnt.time_to_full_formatted().color(usage_color),
+                            width = time_to_full_width
+                        )
+                        .as_str(),
+                    );
+                }
+                ColumnType::Host => {
+                    line.push_str(
+                        format!("{:<width$} ", mnt.host_formatted(), width = host_width).as_str(),
+                    );
+                }
+                ColumnType::Status => {
+                    let status =
+                        format!("{:<width$}", mnt.status_formatted(), width = status_width);
+                    if mnt.status.is_ok() {
+                        line.push_str(&status);
+                    } else {
+                        let color_error = theme.color_usage_high.unwrap_or(Color::Red);
+                        line.push_str(&status.color(color_error).to_string());
+                    }
+                    line.push(' ');
+                }
+                ColumnType::Inodes => {
+                    line.push_str(
+                        format!(
+                            "{:>width$} ",
+                            mnt.inodes_formatted(delimiter).color(inode_color),
+                            width = inodes_width
+                        )
+                        .as_str(),
+                    );
+                }
+                ColumnType::InodesUsed => {
+                    line.push_str(
+                        format!(
+                            "{:>width$} ",
+                            mnt.inodes_used_formatted(delimiter).color(inode_color),
+                            width = inodes_used_width
+                        )
+                        .as_str(),
+                    );
+                }
+                ColumnType::InodesFree => {
+                    line.push_str(
+                        format!(
+                            "{:>width$} ",
+                            mnt.inodes_free_formatted(delimiter).color(inode_color),
+                            width = inodes_free_width
+                        )
+                        .as_str(),
+                    );
+                }
+                ColumnType::InodesUsedPercentage => {
+                    let percentage = format_percentage(mnt.inodes_used_percentage());
+                    line.push_str(format!("{} ", percentage.color(inode_color)).as_str());
+                }
+                ColumnType::InodeBar => {
+                    line.push_str(
+                        format!(
+                            "{:<width$} ",
+                            bar(bar_width, mnt.inodes_used_percentage(), 0.0, theme),
+                            width = bar_width
+                        )
+                        .as_str(),
                     );
                 }
             }
         }
-        if try_println!("{}", line.trim_end()).is_err() {
-            return;
-        }
+        writeln!(w, "{}", line.trim_end())?;
     }
-    if stdout().flush().is_err() {}
+    w.flush()
 }
 
-fn run(args: Args) -> Result<()> {
-    if let Some(color) = args.color {
+fn run(mut args: Args) -> Result<()> {
+    if let Some(color) = &args.color {
         debug!("Bypass tty detection for colors: {:?}", color);
         match color {
             ColorOpt::Auto => {}
@@ -238,105 +504,489 @@ fn run(args: Args) -> Result<()> {
         colored::control::set_override(true);
     }
 
-    match args.subcommand {
-        Some(SubCommand::Completions(completions)) => args::gen_completions(&completions),
-        _ => {
-            let mut theme = Theme::new();
-            theme.columns = args.columns;
+    if args.output_file.is_some() {
+        let unsupported = match &args.subcommand {
+            Some(SubCommand::Completions(_)) => Some("completions"),
+            Some(SubCommand::Config(_)) => Some("config"),
+            Some(SubCommand::Check(_)) => Some("check"),
+            Some(SubCommand::Watch(_)) => Some("watch"),
+            Some(SubCommand::Record(_)) => Some("record"),
+            _ => None,
+        };
+        if let Some(subcommand) = unsupported {
+            bail!(
+                "--output-file is not supported by the {} subcommand",
+                subcommand
+            );
+        }
+    }
 
-            let delimiter = if args.base10 {
-                NumberFormat::Base10
+    // these work without or despite a broken configuration
+    match &args.subcommand {
+        Some(SubCommand::Completions(completions)) => {
+            args::gen_completions(completions);
+            return Ok(());
+        }
+        Some(SubCommand::Schema) => {
+            return emit(&args, |w| output::write_schema(w));
+        }
+        Some(SubCommand::Config(config_args)) => match config_args.command {
+            ConfigCommand::Show => return show_config(args.profile.as_deref()),
+        },
+        _ => {}
+    }
+
+    let config = Config::load(args.profile.as_deref())?;
+    args.merge_config(&config);
+    let mut theme = config.theme();
+    // subcommands add their own columns to the default ones
+    let default_columns = args.columns.is_none() && !config.is_set("columns");
+    if let Some(columns) = &args.columns {
+        theme.columns = columns.clone();
+    }
+    theme.color_by_worse |= args.color_by_worse;
+
+    match &args.subcommand {
+        Some(SubCommand::Check(check_args)) => {
+            let paths = if check_args.paths.is_empty() {
+                &args.paths
+            } else {
+                &check_args.paths
+            };
+            let (state, output) = match mount_query(&args, paths).inodes(false).run() {
+                Ok(mnts) => check::check_mounts(&mnts, check_args, &size_format(&args)),
+                Err(err) => (
+                    check::State::Unknown,
+                    format!("DISK {} - {}", check::State::Unknown, err),
+                ),
+            };
+            output::ignore_broken_pipe(writeln!(stdout().lock(), "{}", output))?;
+            std::process::exit(state.exit_code());
+        }
+        Some(SubCommand::Watch(watch_args)) => {
+            let paths = if watch_args.paths.is_empty() {
+                &args.paths
             } else {
-                NumberFormat::Base2
+                &watch_args.paths
             };
-            let mounts_to_show = if args.all {
-                DisplayFilter::All
-            } else if args.more {
-                DisplayFilter::More
+            let query = mount_query(&args, paths);
+
+            if default_columns {
+                let at = theme
+                    .columns
+                    .iter()
+                    .position(|column| *column == ColumnType::MountedOn)
+                    .unwrap_or(theme.columns.len());
+                theme.columns.splice(
+                    at..at,
+                    [ColumnType::Delta, ColumnType::Rate, ColumnType::TimeToFull],
+                );
+            }
+            let delimiter = number_format(&args);
+
+            watch::watch(
+                Duration::from_secs_f64(watch_args.interval),
+                || Ok(query.run()?),
+                |w, mnts| {
+                    let mut rows = mnts.to_vec();
+                    if args.total {
+                        rows.push(util::calc_total(mnts));
+                    }
+                    display_mounts(
+                        w,
+                        &rows,
+                        &theme,
+                        &delimiter,
+                        args.inodes,
+                        args.no_aliases,
+                        layout::max_width(args.width, args.no_truncate, true),
+                    )
+                },
+            )?;
+        }
+        Some(SubCommand::Record(record_args)) => {
+            let paths = if record_args.paths.is_empty() {
+                &args.paths
             } else {
-                DisplayFilter::from_u8(args.display)
+                &record_args.paths
             };
+            let path = history_path(record_args.history.as_ref())?;
+            let mnts = mount_query(&args, paths).inodes(false).run()?;
 
-            let mut mnts = get_mounts(
-                &mounts_to_show,
-                args.inodes,
-                &args.paths,
-                &args.mounts,
-                args.local,
-            )?;
-            if args.total {
-                mnts.push(util::calc_total(&mnts));
+            let mut history = History::load(&path)?;
+            let now = util::unix_time();
+            if record_args.retention > 0 {
+                history.prune(now.saturating_sub(record_args.retention * 86400));
+            }
+            let recorded = history.record(&mnts, &MountKeys::new(), now);
+            history.save(&path)?;
+            debug!("Recorded {} file systems to {}", recorded, path.display());
+        }
+        Some(SubCommand::Forecast(forecast_args)) => {
+            let path = history_path(forecast_args.history.as_ref())?;
+            let mut forecasts = History::load(&path)?.forecast(args.inodes);
+            let paths = if forecast_args.paths.is_empty() {
+                &args.paths
+            } else {
+                &forecast_args.paths
+            };
+            if !paths.is_empty() {
+                let keys = MountKeys::new();
+                let selected = mount_query(&args, paths)
+                    .run()?
+                    .iter()
+                    .map(|mnt| keys.key(mnt))
+                    .collect::<Vec<_>>();
+                forecasts.retain(|forecast| selected.contains(&forecast.key));
+            }
+
+            let delimiter = number_format(&args);
+            emit(&args, |w| {
+                forecast::display_forecasts(w, &forecasts, &theme, &delimiter)
+            })?;
+        }
+        Some(SubCommand::Diff(diff_args)) => {
+            let old = source_mounts(&args, &diff_args.old)?;
+            let new = source_mounts(&args, &diff_args.new)?;
+            let diffs = diff::diff_mounts(&old, &new);
+
+            let delimiter = number_format(&args);
+            emit(&args, |w| {
+                compare::display_diffs(
+                    w,
+                    &diffs,
+                    &theme,
+                    &delimiter,
+                    args.inodes,
+                    args.no_aliases,
+                    diff_args.significant,
+                )
+            })?;
+        }
+        Some(SubCommand::Fleet(fleet_args)) => {
+            let mut hosts = Vec::new();
+            for path in &fleet_args.snapshots {
+                let mut mnts = match mount_query(&args, &[]).snapshot(path).run() {
+                    Ok(mnts) => mnts,
+                    Err(err) => {
+                        eprintln!("dfrs: {:#}", anyhow!(err));
+                        continue;
+                    }
+                };
+                let host = mnts
+                    .first()
+                    .and_then(|mnt| mnt.host.clone())
+                    .or_else(|| path.file_stem().map(|s| s.to_string_lossy().into_owned()))
+                    .unwrap_or_default();
+                mnts.retain(|mnt| fleet::matches(mnt, fleet_args));
+                for mnt in &mut mnts {
+                    mnt.host.get_or_insert_with(|| host.clone());
+                }
+                hosts.push((host, mnts));
+            }
+
+            if default_columns {
+                theme.columns.insert(0, ColumnType::Host);
+            }
+            let delimiter = number_format(&args);
+            let mnts = hosts
+                .iter()
+                .flat_map(|(_, mnts)| mnts.iter().cloned())
+                .collect::<Vec<_>>();
+            let total = args.total.then(|| util::calc_total(&mnts));
+            // subtotals only make sense as rows of a table
+            let mnts = match args.output {
+                OutputFormat::Table | OutputFormat::Csv | OutputFormat::Tsv => {
+                    fleet::merge_hosts(hosts)
+                }
+                _ => mnts,
+            };
+
+            emit(&args, |w| {
+                write_output(w, &args, &mnts, total.as_ref(), &theme, &delimiter)
+            })?;
+        }
+        _ if args.explain => {
+            if !matches!(
+                args.output,
+                OutputFormat::Table | OutputFormat::Json | OutputFormat::Ndjson
+            ) {
+                bail!("--explain supports the table, json and ndjson output formats");
+            }
+            let explanations = mount_query(&args, &args.paths).explain()?;
+            emit(&args, |w| match args.output {
+                OutputFormat::Table => explain::display_explanations(w, &explanations, &theme),
+                _ => explain::write_explanations_json(
+                    w,
+                    &explanations,
+                    matches!(args.output, OutputFormat::Ndjson),
+                ),
+            })?;
+        }
+        _ => {
+            let delimiter = number_format(&args);
+            let mnts = mount_query(&args, &args.paths).run()?;
+            if args.errors && default_columns {
+                theme.columns = vec![
+                    ColumnType::Filesystem,
+                    ColumnType::Type,
+                    ColumnType::Status,
+                    ColumnType::MountedOn,
+                ];
+            }
+            let total = args.total.then(|| util::calc_total(&mnts));
+
+            emit(&args, |w| {
+                write_output(w, &args, &mnts, total.as_ref(), &theme, &delimiter)
+            })?;
+
+            let failed = mnts
+                .iter()
+                .filter(|mnt| !mnt.status.is_ok())
+                .map(|mnt| format!("{} ({})", mnt.mnt_dir, mnt.status))
+                .collect::<Vec<_>>();
+            if args.strict && !failed.is_empty() {
+                bail!(
+                    "Unable to query {} file system{}: {}",
+                    failed.len(),
+                    if failed.len() == 1 { "" } else { "s" },
+                    failed.join(", ")
+                );
             }
-            display_mounts(&mnts, &theme, &delimiter, args.inodes, args.no_aliases);
         }
     }
 
     Ok(())
 }
 
-fn get_mounts(
-    mounts_to_show: &DisplayFilter,
-    show_inodes: bool,
-    paths: &[PathBuf],
-    mounts: &Path,
-    local_only: bool,
-) -> Result<Vec<Mount>> {
-    let f = File::open(mounts)?;
-
-    let mut mnts = parse_mounts(f)?;
-    mnts.retain(|mount| {
-        mounts_to_show
-            .get_mnt_fsname_filter()
-            .iter()
-            .any(|fsname| util::mnt_matches_filter(mount, fsname))
-    });
-    if local_only {
-        mnts.retain(Mount::is_local);
+/// Atomically write the output to `--output-file` if given, else to stdout
+fn emit<F>(args: &Args, write: F) -> Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> io::Result<()>,
+{
+    if let Some(path) = &args.output_file {
+        if args.color.is_none() && !args.color_always {
+            colored::control::set_override(false);
+        }
+        util::write_atomic(path, write)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    } else {
+        output::ignore_broken_pipe(write(&mut stdout().lock()))?;
     }
+    Ok(())
+}
 
-    for mnt in &mut mnts {
-        mnt.statfs = statfs::statfs(&mnt.mnt_dir[..]).ok();
+/// Print the effective configuration as TOML with the source of each value
+fn display_config(w: &mut dyn Write, config: &Config) -> io::Result<()> {
+    for (key, value, source) in config.entries() {
+        writeln!(w, "{} = {}  # {}", key, value, source)?;
+    }
+    w.flush()
+}
 
-        let (capacity, free) = mnt.statfs.map_or((0, 0), |stat| {
-            if show_inodes {
-                (stat.files(), stat.files_free())
-            } else {
-                (
-                    stat.blocks() * (stat.block_size() as u64),
-                    stat.blocks_available() * (stat.block_size() as u64),
-                )
+/// Print whether every configuration file was found and why it failed to
+/// load, followed by the effective configuration if all of them loaded
+fn show_config(profile: Option<&str>) -> Result<()> {
+    let w = &mut stdout().lock();
+    let mut failed = false;
+    for path in Config::paths() {
+        let status = if !path.exists() {
+            "not found".to_string()
+        } else {
+            match Config::load_paths(std::slice::from_ref(&path), None) {
+                Ok(_) => "loaded".to_string(),
+                Err(dfrs::errors::Error::InvalidConfigValue { key, message, .. }) => {
+                    failed = true;
+                    format!("{}: {}", key, message)
+                }
+                Err(err) => {
+                    failed = true;
+                    let cause = anyhow::Error::from(err).root_cause().to_string();
+                    cause.trim_end().replace('\n', "\n#   ")
+                }
             }
-        });
+        };
+        output::ignore_broken_pipe(writeln!(w, "# {}: {}", path.display(), status))?;
+    }
+    if failed {
+        bail!("Failed to load the configuration");
+    }
+    let config = Config::load(profile)?;
+    output::ignore_broken_pipe(display_config(w, &config))?;
+    Ok(())
+}
 
-        mnt.capacity = capacity;
-        mnt.free = free;
-        mnt.used = capacity - free;
+fn number_format(args: &Args) -> NumberFormat {
+    let format = size_format(args);
+    if args.inodes {
+        format.counts()
+    } else {
+        format
     }
+}
 
-    if !paths.is_empty() {
-        let mut out = Vec::new();
-        for path in paths {
-            let path = match path.canonicalize() {
-                Ok(path) => path,
-                Err(err) => {
-                    eprintln!("dfrs: {}: {}", path.display(), err);
-                    continue;
-                }
-            };
+/// The number format of sizes, regardless of `--inodes`
+fn size_format(args: &Args) -> NumberFormat {
+    let scale = if args.raw_numbers {
+        Scale::Raw
+    } else if let Some(size) = args.block_size {
+        Scale::Blocks(size)
+    } else if args.base10 {
+        Scale::Base10
+    } else {
+        Scale::Base2
+    };
+    NumberFormat {
+        precision: args.precision,
+        iec: args.iec,
+        thousands: args.thousands.as_deref().map(|sep| {
+            sep.chars()
+                .next()
+                .or_else(locale_thousands_separator)
+                .unwrap_or(',')
+        }),
+        ..NumberFormat::new(scale)
+    }
+}
 
-            if let Some(mnt) = util::get_best_mount_match(&path, &mnts) {
-                out.push(mnt.clone());
-            }
+/// Thousands separator of the LC_NUMERIC locale, None for the C locale
+fn locale_thousands_separator() -> Option<char> {
+    // SAFETY: setlocale and localeconv are called before any other thread
+    // formats numbers, the returned struct is only read right away
+    unsafe {
+        libc::setlocale(libc::LC_NUMERIC, c"".as_ptr());
+        let conv = libc::localeconv();
+        if conv.is_null() || (*conv).thousands_sep.is_null() {
+            return None;
         }
-        return Ok(out);
+        std::ffi::CStr::from_ptr((*conv).thousands_sep)
+            .to_str()
+            .ok()
+            .and_then(|sep| sep.chars().next())
     }
+}
+
+/// Mounts of the live system or a snapshot, with the usage in bytes or inodes
+fn source_mounts(args: &Args, source: &Source) -> Result<Vec<Mount>> {
+    match source {
+        Source::Live => Ok(mount_query(args, &[]).run()?),
+        Source::Snapshot(path) => Ok(mount_query(args, &[]).snapshot(path).run()?),
+    }
+}
+
+fn history_path(path: Option<&PathBuf>) -> Result<PathBuf> {
+    path.cloned().or_else(history::default_path).ok_or_else(|| {
+        anyhow!("Unable to locate the history, neither $XDG_STATE_HOME nor $HOME is set")
+    })
+}
+
+fn mount_query(args: &Args, paths: &[PathBuf]) -> MountQuery {
+    let mounts_to_show = if args.all {
+        DisplayFilter::All
+    } else if args.more {
+        DisplayFilter::More
+    } else {
+        DisplayFilter::from_u8(args.display)
+    };
 
-    mnts.sort_by(util::cmp_by_capacity_and_dir_name);
-    Ok(mnts)
+    let mut query = MountQuery::new()
+        .mount_table(&args.mounts)
+        .display_filter(mounts_to_show)
+        .mount_filter(MountFilter {
+            types: args.types.clone(),
+            exclude_types: args.exclude_types.clone(),
+            include_mounts: args.include_mounts.clone(),
+            exclude_mounts: args.exclude_mounts.clone(),
+            devices: args.devices.clone(),
+        })
+        .inodes(args.inodes)
+        .reserved_as_used(args.reserved_as_used)
+        .local_only(args.local)
+        .reverse(args.reverse)
+        .top(args.top)
+        .predicate(args.predicate.clone())
+        .errors_only(args.errors)
+        .dedupe(args.dedupe)
+        .timeout((args.timeout > 0.0).then(|| Duration::from_secs_f64(args.timeout)));
+    if let Some(columns) = &args.sort {
+        query = query.sort_by_columns(columns.iter().cloned());
+    }
+    if let Some(input) = &args.input {
+        query = query.snapshot(input);
+    }
+    if paths.is_empty() {
+        query
+    } else if args.input.is_some() {
+        // paths refer to the host the snapshot was taken on
+        query.paths(paths)
+    } else {
+        query.paths(paths.iter().filter_map(|path| match path.canonicalize() {
+            Ok(path) => Some(path),
+            Err(err) => {
+                eprintln!("dfrs: {}: {}", path.display(), err);
+                None
+            }
+        }))
+    }
+}
+
+fn write_output(
+    w: &mut dyn Write,
+    args: &Args,
+    mnts: &[Mount],
+    total: Option<&Mount>,
+    theme: &Theme,
+    delimiter: &NumberFormat,
+) -> io::Result<()> {
+    let rows = mnts.iter().chain(total).cloned().collect::<Vec<_>>();
+    match args.output {
+        OutputFormat::Table => display_mounts(
+            w,
+            &rows,
+            theme,
+            delimiter,
+            args.inodes,
+            args.no_aliases,
+            layout::max_width(args.width, args.no_truncate, args.output_file.is_none()),
+        ),
+        OutputFormat::Json => output::write_json(w, &Snapshot::capture(mnts, total, args.inodes)),
+        OutputFormat::Ndjson => {
+            output::write_ndjson(w, &Snapshot::capture(mnts, total, args.inodes))
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let separator = match args.output {
+                OutputFormat::Tsv => output::Separator::Tab,
+                _ => output::Separator::Comma,
+            };
+            output::write_delimited(
+                w,
+                &rows,
+                &theme.columns,
+                separator,
+                delimiter,
+                args.inodes,
+                args.no_aliases,
+            )
+        }
+        OutputFormat::Prometheus => output::write_prometheus(w, mnts, false),
+        OutputFormat::Openmetrics => output::write_prometheus(w, mnts, true),
+    }
 }
 
 fn main() {
+    if df::requested(std::env::args_os()) {
+        std::process::exit(df::main());
+    }
+
     let args = Args::parse();
+    if args.df_compat {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--df-compat has to be the first argument",
+            )
+            .exit();
+    }
 
     let logging = if args.verbose { "debug" } else { "info" };
 
@@ -1,16 +1,137 @@
-use crate::errors::*;
-
-use crate::args::NumberFormat;
+use crate::errors::{Error, Result};
 use crate::theme::Theme;
-use crate::util::{format_count, lvm_alias};
+use crate::util::{format_duration, format_signed, lvm_alias, NumberFormat};
 
 use colored::Color;
-use std::fs::File;
-use std::io::BufRead;
-use std::io::BufReader;
+use log::{debug, warn};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+use std::num::ParseIntError;
+use std::path::Path;
+use std::str::FromStr;
+
+use nix::errno::Errno;
+use nix::sys::statfs;
+
+/// Raw numbers as reported by statfs(2) for a mounted file system.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatfsInfo {
+    pub block_size: u64,
+    pub blocks: u64,
+    pub blocks_free: u64,
+    pub blocks_available: u64,
+    pub files: u64,
+    pub files_free: u64,
+}
+
+/// Outcome of querying the usage of a mount.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    #[default]
+    Ok,
+    /// statfs(2) did not return within the timeout, e.g., for a file system
+    /// on an unreachable server
+    Stale,
+    /// The file handle of a network file system went stale (`ESTALE`)
+    StaleHandle,
+    /// The file system reported an I/O error (`EIO`)
+    IoError,
+    Failed(Errno),
+}
+
+impl Status {
+    pub const fn from_errno(errno: Errno) -> Self {
+        match errno {
+            Errno::ESTALE => Self::StaleHandle,
+            Errno::EIO => Self::IoError,
+            errno => Self::Failed(errno),
+        }
+    }
+
+    pub const fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok)
+    }
+
+    /// Short identifier of the status
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Stale => "stale",
+            Self::StaleHandle => "stale_handle",
+            Self::IoError => "io_error",
+            Self::Failed(_) => "error",
+        }
+    }
+
+    /// Rebuild a status from its name and, for other failures, the errno
+    pub fn from_name(name: &str, errno: Option<i32>) -> Option<Self> {
+        Some(match name {
+            "ok" => Self::Ok,
+            "stale" => Self::Stale,
+            "stale_handle" => Self::StaleHandle,
+            "io_error" => Self::IoError,
+            "error" => Self::Failed(Errno::from_raw(errno?)),
+            _ => return None,
+        })
+    }
+
+    pub const fn errno(&self) -> Option<Errno> {
+        match self {
+            Self::Ok | Self::Stale => None,
+            Self::StaleHandle => Some(Errno::ESTALE),
+            Self::IoError => Some(Errno::EIO),
+            Self::Failed(errno) => Some(*errno),
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ok => "ok",
+            Self::Stale => "stale",
+            Self::StaleHandle => "stale file handle",
+            Self::IoError => "I/O error",
+            Self::Failed(errno) => errno.desc(),
+        })
+    }
+}
+
+impl StatfsInfo {
+    pub fn query<P: AsRef<Path>>(path: P) -> nix::Result<Self> {
+        statfs::statfs(path.as_ref()).map(|stat| Self::from(&stat))
+    }
+}
+
+impl From<&statfs::Statfs> for StatfsInfo {
+    #[allow(clippy::useless_conversion, clippy::unnecessary_cast)]
+    fn from(stat: &statfs::Statfs) -> Self {
+        Self {
+            block_size: stat.block_size() as u64,
+            blocks: stat.blocks().into(),
+            blocks_free: stat.blocks_free().into(),
+            blocks_available: stat.blocks_available().into(),
+            files: stat.files().into(),
+            files_free: stat.files_free().into(),
+        }
+    }
+}
 
-#[allow(dead_code)]
-#[derive(Clone)]
+/// Change of the used amount between samples of the same mount.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trend {
+    /// Change of the used amount since the previous sample
+    pub delta: i64,
+    /// Change of the used amount per second over the recent samples
+    pub rate: f64,
+    /// Estimated seconds until the file system is full at the current rate
+    pub time_to_full: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mount {
     pub mnt_fsname: String,
     pub mnt_dir: String,
@@ -18,10 +139,44 @@ pub struct Mount {
     pub mnt_opts: String,
     pub mnt_freq: i32,
     pub mnt_passno: i32,
+    pub mnt_id: Option<u32>,
+    pub mnt_parent_id: Option<u32>,
+    pub mnt_dev: Option<(u32, u32)>,
+    pub mnt_root: Option<String>,
+    pub mnt_optional_fields: Vec<String>,
+    pub mnt_super_opts: Option<String>,
     pub capacity: u64,
-    pub free: u64,
+    /// Space available to unprivileged users
+    pub available: u64,
+    /// Free space only available to the superuser
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub reserved: u64,
+    /// Space in use, not counting the reserved space
     pub used: u64,
-    pub statfs: Option<nix::sys::statfs::Statfs>,
+    pub statfs: Option<StatfsInfo>,
+    /// Whether querying the usage succeeded
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub status: Status,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub trend: Option<Trend>,
+    /// Further mount points of the same file system collapsed into this one
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub other_dirs: Vec<String>,
+    /// Host the mount belongs to when merging the snapshots of several hosts
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub host: Option<String>,
+}
+
+impl AsRef<Self> for Mount {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+impl AsMut<Self> for Mount {
+    fn as_mut(&mut self) -> &mut Self {
+        self
+    }
 }
 
 impl Mount {
@@ -34,40 +189,197 @@ impl Mount {
         lvm.unwrap_or_else(|| self.mnt_fsname.clone())
     }
 
+    /// Used share of the space available to unprivileged users, like
+    /// GNU df: `used / (used + available)`
     pub fn used_percentage(&self) -> Option<f32> {
-        match self.capacity {
+        match self.used + self.available {
             0 => None,
-            _ => Some(100.0 - self.free as f32 * 100.0 / self.capacity as f32),
+            total => Some(self.used as f32 * 100.0 / total as f32),
         }
     }
 
-    pub fn free_percentage(&self) -> Option<f32> {
-        match self.free {
+    pub fn available_percentage(&self) -> Option<f32> {
+        match self.used + self.available {
             0 => None,
-            _ => Some(self.free as f32 * 100.0 / self.capacity as f32),
+            total => Some(self.available as f32 * 100.0 / total as f32),
         }
     }
 
+    /// Share of the total size reserved for the superuser
+    pub fn reserved_percentage(&self) -> Option<f32> {
+        match self.capacity {
+            0 => None,
+            _ => Some(self.reserved as f32 * 100.0 / self.capacity as f32),
+        }
+    }
+
+    /// Free space including the reserved space
+    pub const fn free(&self) -> u64 {
+        self.available + self.reserved
+    }
+
+    pub fn inodes(&self) -> Option<u64> {
+        self.statfs.map(|stat| stat.files)
+    }
+
+    pub fn inodes_free(&self) -> Option<u64> {
+        self.statfs.map(|stat| stat.files_free)
+    }
+
+    pub fn inodes_used(&self) -> Option<u64> {
+        self.statfs
+            .map(|stat| stat.files.saturating_sub(stat.files_free))
+    }
+
+    pub fn inodes_used_percentage(&self) -> Option<f32> {
+        match self.statfs {
+            Some(stat) if stat.files > 0 => {
+                Some(100.0 - stat.files_free as f32 * 100.0 / stat.files as f32)
+            }
+            _ => None,
+        }
+    }
+
+    /// Used percentage as a whole number rounded up, as printed by GNU df
+    pub fn used_percentage_rounded_up(&self) -> Option<u64> {
+        percentage_rounded_up(self.used, self.available)
+    }
+
+    /// Inode used percentage as a whole number rounded up, as printed by
+    /// GNU df
+    pub fn inodes_used_percentage_rounded_up(&self) -> Option<u64> {
+        self.statfs.and_then(|stat| {
+            percentage_rounded_up(stat.files.saturating_sub(stat.files_free), stat.files_free)
+        })
+    }
+
+    /// The higher of the block and the inode used percentage
+    pub fn worse_used_percentage(&self) -> Option<f32> {
+        let blocks = match self.statfs {
+            Some(stat) if stat.blocks > 0 => {
+                let used = stat.blocks.saturating_sub(stat.blocks_free);
+                Some(used as f32 * 100.0 / (used + stat.blocks_available).max(1) as f32)
+            }
+            _ => self.used_percentage(),
+        };
+        match (blocks, self.inodes_used_percentage()) {
+            (Some(blocks), Some(inodes)) => Some(blocks.max(inodes)),
+            (blocks, inodes) => blocks.or(inodes),
+        }
+    }
+
+    /// Fill capacity, available, reserved and used from the statfs
+    /// numbers, either in bytes or in inodes.
+    pub fn compute_usage(&mut self, inodes: bool) {
+        let (capacity, free, available) = self.statfs.map_or((0, 0, 0), |stat| {
+            if inodes {
+                (stat.files, stat.files_free, stat.files_free)
+            } else {
+                (
+                    stat.blocks * stat.block_size,
+                    stat.blocks_free * stat.block_size,
+                    stat.blocks_available * stat.block_size,
+                )
+            }
+        });
+
+        self.capacity = capacity;
+        self.available = available;
+        self.reserved = free.saturating_sub(available);
+        self.used = capacity.saturating_sub(free);
+    }
+
+    /// Count the reserved space as used, so percentages are relative to
+    /// the total size
+    pub const fn count_reserved_as_used(&mut self) {
+        self.used += self.reserved;
+        self.reserved = 0;
+    }
+
+    /// Identity of the underlying file system, shared by bind mounts and
+    /// repeated mounts of the same file system on a host
+    pub fn fs_key(&self) -> Option<(Option<&str>, (u32, u32))> {
+        self.mnt_dev.map(|dev| (self.host.as_deref(), dev))
+    }
+
+    /// The mount point followed by those of collapsed duplicates
+    pub fn dirs_formatted(&self) -> String {
+        std::iter::once(&self.mnt_dir)
+            .chain(&self.other_dirs)
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn status_formatted(&self) -> String {
+        self.status.to_string()
+    }
+
+    pub fn host_formatted(&self) -> String {
+        self.host.clone().unwrap_or_else(|| "-".to_string())
+    }
+
     pub fn capacity_formatted(&self, delimiter: &NumberFormat) -> String {
-        format_count(self.capacity as f64, delimiter.get_powers_of())
+        delimiter.format(self.capacity)
     }
 
-    pub fn free_formatted(&self, delimiter: &NumberFormat) -> String {
-        format_count(self.free as f64, delimiter.get_powers_of())
+    pub fn available_formatted(&self, delimiter: &NumberFormat) -> String {
+        delimiter.format(self.available)
     }
 
     pub fn used_formatted(&self, delimiter: &NumberFormat) -> String {
-        format_count(self.used as f64, delimiter.get_powers_of())
+        delimiter.format(self.used)
+    }
+
+    pub fn reserved_formatted(&self, delimiter: &NumberFormat) -> String {
+        delimiter.format(self.reserved)
+    }
+
+    pub fn free_formatted(&self, delimiter: &NumberFormat) -> String {
+        delimiter.format(self.free())
+    }
+
+    pub fn inodes_formatted(&self, delimiter: &NumberFormat) -> String {
+        self.inodes()
+            .map_or_else(|| "-".to_string(), |n| delimiter.counts().format(n))
+    }
+
+    pub fn inodes_free_formatted(&self, delimiter: &NumberFormat) -> String {
+        self.inodes_free()
+            .map_or_else(|| "-".to_string(), |n| delimiter.counts().format(n))
+    }
+
+    pub fn inodes_used_formatted(&self, delimiter: &NumberFormat) -> String {
+        self.inodes_used()
+            .map_or_else(|| "-".to_string(), |n| delimiter.counts().format(n))
+    }
+
+    pub fn delta_formatted(&self, delimiter: &NumberFormat) -> String {
+        self.trend.map_or_else(
+            || "-".to_string(),
+            |trend| format_signed(trend.delta as f64, delimiter),
+        )
+    }
+
+    pub fn rate_formatted(&self, delimiter: &NumberFormat) -> String {
+        self.trend.map_or_else(
+            || "-".to_string(),
+            |trend| format!("{}/s", format_signed(trend.rate, delimiter)),
+        )
+    }
+
+    pub fn time_to_full_formatted(&self) -> String {
+        self.trend
+            .and_then(|trend| trend.time_to_full)
+            .map_or_else(|| "-".to_string(), format_duration)
     }
 
     pub fn usage_color(&self, theme: &Theme) -> Color {
-        match &self.used_percentage() {
-            Some(p) if p >= &theme.threshold_usage_high => &theme.color_usage_high,
-            Some(p) if p >= &theme.threshold_usage_medium => &theme.color_usage_medium,
-            Some(_) => &theme.color_usage_low,
-            _ => &theme.color_usage_void,
+        if theme.color_by_worse {
+            theme.usage_color(self.worse_used_percentage())
+        } else {
+            theme.usage_color(self.used_percentage())
         }
-        .unwrap_or(Color::White)
     }
 
     #[inline]
@@ -111,58 +423,227 @@ impl Mount {
             mnt_opts,
             mnt_freq,
             mnt_passno,
+            mnt_id: None,
+            mnt_parent_id: None,
+            mnt_dev: None,
+            mnt_root: None,
+            mnt_optional_fields: Vec::new(),
+            mnt_super_opts: None,
             capacity: 0,
-            free: 0,
+            available: 0,
+            reserved: 0,
             used: 0,
             statfs: None,
+            status: Status::Ok,
+            trend: None,
+            other_dirs: Vec::new(),
+            host: None,
+        }
+    }
+}
+
+/// `used / (used + available)` in percent rounded up, None without either
+fn percentage_rounded_up(used: u64, available: u64) -> Option<u64> {
+    let total = u128::from(used) + u128::from(available);
+    (total > 0).then(|| ((u128::from(used) * 100).div_ceil(total)) as u64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MountTableFormat {
+    /// Flat table as found in /proc/self/mounts or /etc/mtab
+    Mounts,
+    /// Extended table as found in /proc/self/mountinfo
+    MountInfo,
+}
+
+impl MountTableFormat {
+    pub fn detect(line: &str) -> Self {
+        let mut fields = line.split_whitespace();
+        let is_mountinfo = fields.next().is_some_and(|f| f.parse::<u32>().is_ok())
+            && fields.next().is_some_and(|f| f.parse::<u32>().is_ok())
+            && fields.next().is_some_and(|f| f.contains(':'))
+            && fields.any(|f| f == "-");
+        if is_mountinfo {
+            Self::MountInfo
+        } else {
+            Self::Mounts
+        }
+    }
+
+    pub fn parse_line(self, line: &str) -> Result<Mount> {
+        match self {
+            Self::Mounts => parse_mount_line(line),
+            Self::MountInfo => parse_mountinfo_line(line),
+        }
+    }
+}
+
+/// Decode the `\ooo` octal escapes the kernel uses for whitespace and
+/// backslashes in mount table fields (e.g., `\040` for a space).
+pub(crate) fn unescape_octal(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            let digits = &bytes[i + 1..bytes.len().min(i + 4)];
+            if digits.len() == 3 && digits.iter().all(|b| (b'0'..=b'7').contains(b)) {
+                let value = digits
+                    .iter()
+                    .fold(0u32, |acc, b| acc * 8 + u32::from(b - b'0'));
+                if let Ok(value) = u8::try_from(value) {
+                    out.push(value);
+                    i += 4;
+                    continue;
+                }
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Encode whitespace and backslashes as `\ooo` octal escapes, the inverse
+/// of [`unescape_octal`].
+pub(crate) fn escape_octal(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            ' ' | '\t' | '\n' | '\\' => out.push_str(&format!("\\{:03o}", c as u32)),
+            _ => out.push(c),
         }
     }
+    out
+}
+
+fn next_field<'a>(
+    fields: &mut impl Iterator<Item = &'a str>,
+    name: &'static str,
+) -> Result<&'a str> {
+    fields.next().ok_or(Error::MissingField(name))
+}
+
+fn parse_field<T>(value: &str, name: &'static str) -> Result<T>
+where
+    T: FromStr<Err = ParseIntError>,
+{
+    value.parse::<T>().map_err(|err| Error::InvalidField {
+        field: name,
+        value: value.to_string(),
+        source: Some(err),
+    })
+}
+
+fn parse_dev(dev: &str) -> Result<(u32, u32)> {
+    let (major, minor) = dev.split_once(':').ok_or_else(|| Error::InvalidField {
+        field: "major:minor",
+        value: dev.to_string(),
+        source: None,
+    })?;
+    Ok((parse_field(major, "major")?, parse_field(minor, "minor")?))
 }
 
-fn parse_mount_line(line: &str) -> Result<Mount> {
+/// Parse a single line of the flat mount table format (e.g., /proc/self/mounts).
+pub fn parse_mount_line(line: &str) -> Result<Mount> {
     let mut mnt_a = line.split_whitespace();
     Ok(Mount::new(
-        mnt_a
-            .next()
-            .ok_or_else(|| anyhow!("Missing value fsname"))?
-            .into(),
-        mnt_a
-            .next()
-            .ok_or_else(|| anyhow!("Missing value dir"))?
-            .into(),
-        mnt_a
-            .next()
-            .ok_or_else(|| anyhow!("Missing value type"))?
-            .into(),
-        mnt_a
-            .next()
-            .ok_or_else(|| anyhow!("Missing value opts"))?
-            .into(),
-        mnt_a
-            .next()
-            .ok_or_else(|| anyhow!("Missing value freq"))?
-            .parse::<i32>()?,
-        mnt_a
-            .next()
-            .ok_or_else(|| anyhow!("Missing value passno"))?
-            .parse::<i32>()?,
+        unescape_octal(next_field(&mut mnt_a, "fsname")?),
+        unescape_octal(next_field(&mut mnt_a, "dir")?),
+        unescape_octal(next_field(&mut mnt_a, "type")?),
+        unescape_octal(next_field(&mut mnt_a, "opts")?),
+        parse_field(next_field(&mut mnt_a, "freq")?, "freq")?,
+        parse_field(next_field(&mut mnt_a, "passno")?, "passno")?,
     ))
 }
 
-pub fn parse_mounts(f: File) -> Result<Vec<Mount>> {
-    BufReader::new(f)
-        .lines()
-        .map(|line| {
-            parse_mount_line(&line?)
-                .context("Failed to parse mount line")
-                .map_err(Error::from)
+/// Parse a single line of the extended mountinfo format (e.g., /proc/self/mountinfo).
+pub fn parse_mountinfo_line(line: &str) -> Result<Mount> {
+    let mut mnt_a = line.split_whitespace();
+    let mnt_id = parse_field(next_field(&mut mnt_a, "mount id")?, "mount id")?;
+    let mnt_parent_id = parse_field(next_field(&mut mnt_a, "parent id")?, "parent id")?;
+    let mnt_dev = parse_dev(next_field(&mut mnt_a, "major:minor")?)?;
+    let mnt_root = next_field(&mut mnt_a, "root")?;
+    let mnt_dir = next_field(&mut mnt_a, "dir")?;
+    let mnt_opts = next_field(&mut mnt_a, "opts")?;
+    let mnt_optional_fields = mnt_a
+        .by_ref()
+        .take_while(|field| *field != "-")
+        .map(unescape_octal)
+        .collect();
+    let mnt_type = next_field(&mut mnt_a, "type")?;
+    let mnt_fsname = next_field(&mut mnt_a, "fsname")?;
+    let mnt_super_opts = next_field(&mut mnt_a, "super opts")?;
+
+    let mut mnt = Mount::new(
+        unescape_octal(mnt_fsname),
+        unescape_octal(mnt_dir),
+        unescape_octal(mnt_type),
+        unescape_octal(mnt_opts),
+        0,
+        0,
+    );
+    mnt.mnt_id = Some(mnt_id);
+    mnt.mnt_parent_id = Some(mnt_parent_id);
+    mnt.mnt_dev = Some(mnt_dev);
+    mnt.mnt_root = Some(unescape_octal(mnt_root));
+    mnt.mnt_optional_fields = mnt_optional_fields;
+    mnt.mnt_super_opts = Some(unescape_octal(mnt_super_opts));
+    Ok(mnt)
+}
+
+/// Parse a mount table in either the flat mounts or the mountinfo format.
+///
+/// The format is detected from the first line that parses in the format it
+/// looks like. Malformed lines, including those that are not valid UTF-8,
+/// are skipped with a warning instead of failing the whole table.
+pub fn parse_mounts<R: Read>(f: R) -> Result<Vec<Mount>> {
+    let lines = BufReader::new(f)
+        .split(b'\n')
+        .collect::<io::Result<Vec<_>>>()
+        .map_err(Error::ReadMountTable)?;
+    let lines = lines
+        .into_iter()
+        .enumerate()
+        .filter_map(|(idx, line)| match String::from_utf8(line) {
+            // line endings as understood by BufRead::lines
+            Ok(line) => Some((
+                idx,
+                line.strip_suffix('\r').map(str::to_string).unwrap_or(line),
+            )),
+            Err(err) => {
+                warn!("Skipping malformed mount line {}: {}", idx + 1, err);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    let format = lines
+        .iter()
+        .find_map(|(_, line)| {
+            let format = MountTableFormat::detect(line);
+            format.parse_line(line).ok().map(|_| format)
+        })
+        .unwrap_or(MountTableFormat::Mounts);
+    debug!("Detected mount table format: {:?}", format);
+
+    Ok(lines
+        .iter()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(idx, line)| match format.parse_line(line) {
+            Ok(mnt) => Some(mnt),
+            Err(err) => {
+                warn!("Skipping malformed mount line {}: {}", idx + 1, err);
+                None
+            }
         })
-        .collect::<Result<Vec<_>>>()
+        .collect())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::Scale;
 
     #[test]
     fn parse_mounts() {
@@ -182,16 +663,11 @@ none /cgroup2 cgroup2 rw,relatime 0 0
 "#;
         let mounts = file
             .lines()
-            .map(|line| {
-                parse_mount_line(line)
-                    .context("Failed to parse mount line")
-                    .map_err(Error::from)
-            })
+            .map(parse_mount_line)
             .collect::<Result<Vec<_>>>()
             .unwrap();
         assert_eq!(mounts.len(), 13);
 
-        // nix::sys::statfs::Statfs doesn't have PartialEq
         let mnt = &mounts[0];
         assert_eq!(mnt.mnt_fsname.as_str(), "sysfs");
         assert_eq!(mnt.mnt_dir.as_str(), "/sys");
@@ -200,9 +676,177 @@ none /cgroup2 cgroup2 rw,relatime 0 0
         assert_eq!(mnt.mnt_freq, 0);
         assert_eq!(mnt.mnt_passno, 0);
         assert_eq!(mnt.capacity, 0);
-        assert_eq!(mnt.free, 0);
+        assert_eq!(mnt.available, 0);
         assert_eq!(mnt.used, 0);
         assert!(mnt.statfs.is_none());
+        assert!(mnt.mnt_id.is_none());
+        assert!(mnt.mnt_dev.is_none());
+    }
+
+    #[test]
+    fn parse_mountinfo() {
+        let file = r#"23 28 0:22 / /proc rw,nosuid,nodev,noexec,relatime shared:13 - proc proc rw,hidepid=2
+25 28 0:6 / /dev rw,nosuid,relatime shared:2 - devtmpfs udev rw,size=2009144k,nr_inodes=502286,mode=755
+28 1 254:0 / / rw,relatime shared:1 - ext4 /dev/mapper/vg0-root rw,errors=remount-ro
+29 28 254:1 / /boot rw,relatime shared:30 master:4 - ext4 /dev/mapper/vg0-boot rw
+30 28 254:0 /srv/data /data rw,relatime - ext4 /dev/mapper/vg0-root rw,errors=remount-ro
+"#;
+        let mounts = file
+            .lines()
+            .map(parse_mountinfo_line)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(mounts.len(), 5);
+
+        let mnt = &mounts[2];
+        assert_eq!(mnt.mnt_id, Some(28));
+        assert_eq!(mnt.mnt_parent_id, Some(1));
+        assert_eq!(mnt.mnt_dev, Some((254, 0)));
+        assert_eq!(mnt.mnt_root.as_deref(), Some("/"));
+        assert_eq!(mnt.mnt_dir.as_str(), "/");
+        assert_eq!(mnt.mnt_opts.as_str(), "rw,relatime");
+        assert_eq!(mnt.mnt_optional_fields, vec!["shared:1"]);
+        assert_eq!(mnt.mnt_type.as_str(), "ext4");
+        assert_eq!(mnt.mnt_fsname.as_str(), "/dev/mapper/vg0-root");
+        assert_eq!(mnt.mnt_super_opts.as_deref(), Some("rw,errors=remount-ro"));
+
+        let mnt = &mounts[3];
+        assert_eq!(mnt.mnt_optional_fields, vec!["shared:30", "master:4"]);
+
+        let mnt = &mounts[4];
+        assert_eq!(mnt.mnt_root.as_deref(), Some("/srv/data"));
+        assert_eq!(mnt.mnt_dir.as_str(), "/data");
+    }
+
+    #[test]
+    fn parse_mountinfo_missing_separator() {
+        assert!(parse_mountinfo_line("28 1 254:0 / / rw,relatime shared:1").is_err());
+    }
+
+    #[test]
+    fn percentages_rounded_up() {
+        let mut mnt = Mount::named("/dev/vda".to_string());
+        assert_eq!(mnt.used_percentage_rounded_up(), None);
+        assert_eq!(mnt.inodes_used_percentage_rounded_up(), None);
+        mnt.used = 20_340_248;
+        mnt.available = 80_462_028;
+        assert_eq!(mnt.used_percentage_rounded_up(), Some(21));
+        mnt.used = 1;
+        mnt.available = 3;
+        assert_eq!(mnt.used_percentage_rounded_up(), Some(25));
+        mnt.statfs = Some(StatfsInfo {
+            files: 1000,
+            files_free: 999,
+            ..StatfsInfo::default()
+        });
+        assert_eq!(mnt.inodes_used_percentage_rounded_up(), Some(1));
+    }
+
+    #[test]
+    fn unescape_octal_whitespace_and_backslash() {
+        assert_eq!(unescape_octal(r"/mnt/my\040disk"), "/mnt/my disk");
+        assert_eq!(unescape_octal(r"/mnt/tab\011ed"), "/mnt/tab\ted");
+        assert_eq!(unescape_octal(r"/mnt/new\012line"), "/mnt/new\nline");
+        assert_eq!(unescape_octal(r"/mnt/back\134slash"), "/mnt/back\\slash");
+    }
+
+    #[test]
+    fn unescape_octal_invalid_sequences() {
+        assert_eq!(unescape_octal(r"/mnt/a\"), r"/mnt/a\");
+        assert_eq!(unescape_octal(r"/mnt/a\04"), r"/mnt/a\04");
+        assert_eq!(unescape_octal(r"/mnt/a\089"), r"/mnt/a\089");
+        assert_eq!(unescape_octal(r"/mnt/a\777"), r"/mnt/a\777");
+    }
+
+    #[test]
+    fn escape_octal_roundtrip() {
+        let field = "/mnt/my disk\twith\nback\\slash";
+        assert_eq!(
+            escape_octal(field),
+            r"/mnt/my\040disk\011with\012back\134slash"
+        );
+        assert_eq!(unescape_octal(&escape_octal(field)), field);
+    }
+
+    #[test]
+    fn parse_mount_line_escaped_fields() {
+        let mnt =
+            parse_mount_line(r"/dev/sdb1 /media/USB\040Stick\011\012\134 vfat rw,relatime 0 0")
+                .unwrap();
+        assert_eq!(mnt.mnt_fsname.as_str(), "/dev/sdb1");
+        assert_eq!(mnt.mnt_dir.as_str(), "/media/USB Stick\t\n\\");
+    }
+
+    #[test]
+    fn parse_mountinfo_line_escaped_fields() {
+        let mnt = parse_mountinfo_line(
+            r"40 28 8:17 /with\040space /media/my\040disk rw - ext4 /dev/disk\134x rw",
+        )
+        .unwrap();
+        assert_eq!(mnt.mnt_root.as_deref(), Some("/with space"));
+        assert_eq!(mnt.mnt_dir.as_str(), "/media/my disk");
+        assert_eq!(mnt.mnt_fsname.as_str(), "/dev/disk\\x");
+    }
+
+    #[test]
+    fn parse_mounts_skips_malformed_lines() {
+        let file = r#"/dev/sda1 / ext4 rw,relatime 0 0
+/dev/sda2 /broken
+/dev/sda3 /home ext4 rw,relatime x 0
+
+/dev/sda4 /srv ext4 rw,relatime 0 0
+"#;
+        let mounts = super::parse_mounts(file.as_bytes()).unwrap();
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0].mnt_dir.as_str(), "/");
+        assert_eq!(mounts[1].mnt_dir.as_str(), "/srv");
+    }
+
+    #[test]
+    fn parse_mounts_skips_malformed_mountinfo_lines() {
+        let file = r#"28 1 254:0 / / rw,relatime shared:1 - ext4 /dev/vda rw
+29 28 254:x / /boot rw,relatime - ext4 /dev/vdb rw
+30 28 254:2 / /srv rw,relatime - ext4 /dev/vdc rw
+"#;
+        let mounts = super::parse_mounts(file.as_bytes()).unwrap();
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[1].mnt_dir.as_str(), "/srv");
+    }
+
+    #[test]
+    fn parse_mounts_skips_invalid_utf8() {
+        let mut file = b"/dev/sda1 / ext4 rw,relatime 0 0\n".to_vec();
+        file.extend_from_slice(b"/dev/sda2 /mnt/\xff ext4 rw 0 0\r\n");
+        file.extend_from_slice(b"/dev/sda3 /srv ext4 rw,relatime 0 0\r\n");
+        let mounts = super::parse_mounts(file.as_slice()).unwrap();
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[1].mnt_dir.as_str(), "/srv");
+        assert_eq!(mounts[1].mnt_passno, 0);
+    }
+
+    #[test]
+    fn parse_mounts_detects_format_past_leading_junk() {
+        let file = r#"
+28 1 254:x / / rw,relatime shared:1 - ext4 /dev/vda rw
+29 28 254:1 / /boot rw,relatime - ext4 /dev/vdb rw
+30 28 254:2 / /srv rw,relatime - ext4 /dev/vdc rw
+"#;
+        let mounts = super::parse_mounts(file.as_bytes()).unwrap();
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0].mnt_dev, Some((254, 1)));
+        assert_eq!(mounts[1].mnt_dir.as_str(), "/srv");
+    }
+
+    #[test]
+    fn detect_mount_table_format() {
+        assert_eq!(
+            MountTableFormat::detect("28 1 254:0 / / rw,relatime shared:1 - ext4 /dev/vda rw"),
+            MountTableFormat::MountInfo
+        );
+        assert_eq!(
+            MountTableFormat::detect("/dev/vda / ext4 rw,relatime 0 0"),
+            MountTableFormat::Mounts
+        );
     }
 
     #[test]
@@ -218,4 +862,49 @@ none /cgroup2 cgroup2 rw,relatime 0 0
         mnt.mnt_type = String::from("btrfs");
         assert!(mnt.is_local());
     }
+
+    #[test]
+    fn inode_usage_alongside_blocks() {
+        let mut mnt = Mount::named("foo".into());
+        assert_eq!(mnt.inodes_used_percentage(), None);
+        assert_eq!(mnt.inodes_formatted(&NumberFormat::new(Scale::Raw)), "-");
+        mnt.statfs = Some(StatfsInfo {
+            block_size: 4096,
+            blocks: 100,
+            blocks_free: 80,
+            blocks_available: 60,
+            files: 1000,
+            files_free: 100,
+        });
+        mnt.compute_usage(false);
+        assert_eq!(mnt.used_percentage(), Some(25.0));
+        assert_eq!(mnt.reserved, 20 * 4096);
+        assert_eq!(mnt.inodes_used(), Some(900));
+        assert_eq!(mnt.inodes_used_percentage(), Some(90.0));
+        assert_eq!(mnt.worse_used_percentage(), Some(90.0));
+
+        let mut theme = Theme::new();
+        assert_eq!(mnt.usage_color(&theme), Color::Green);
+        theme.color_by_worse = true;
+        assert_eq!(mnt.usage_color(&theme), Color::Red);
+    }
+
+    #[test]
+    fn percentages_of_full_file_system() {
+        let mut mnt = Mount::named("foo".into());
+        mnt.statfs = Some(StatfsInfo {
+            block_size: 1,
+            blocks: 100,
+            blocks_free: 5,
+            blocks_available: 0,
+            ..Default::default()
+        });
+        mnt.compute_usage(false);
+        assert_eq!(mnt.used_percentage(), Some(100.0));
+        assert_eq!(mnt.available_percentage(), Some(0.0));
+        assert_eq!(mnt.reserved_percentage(), Some(5.0));
+        assert_eq!(mnt.free(), 5);
+
+        assert_eq!(Mount::named("proc".into()).available_percentage(), None);
+    }
 }
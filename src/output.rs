@@ -0,0 +1,407 @@
+use dfrs::column::ColumnType;
+use dfrs::mount::StatfsInfo;
+use dfrs::snapshot::Snapshot;
+use dfrs::util::NumberFormat;
+use dfrs::Mount;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Treat a closed stdout (e.g., piped into `head`) as success
+pub fn ignore_broken_pipe(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+pub fn write_json<W: Write + ?Sized>(w: &mut W, snapshot: &Snapshot) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *w, snapshot)?;
+    writeln!(w)?;
+    w.flush()
+}
+
+pub fn write_ndjson<W: Write + ?Sized>(w: &mut W, snapshot: &Snapshot) -> io::Result<()> {
+    for record in snapshot.ndjson_records() {
+        serde_json::to_writer(&mut *w, &record)?;
+        writeln!(w)?;
+    }
+    w.flush()
+}
+
+pub fn write_schema<W: Write + ?Sized>(w: &mut W) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *w, &dfrs::snapshot::json_schema())?;
+    writeln!(w)?;
+    w.flush()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Separator {
+    Comma,
+    Tab,
+}
+
+impl Separator {
+    const fn as_char(self) -> char {
+        match self {
+            Self::Comma => ',',
+            Self::Tab => '\t',
+        }
+    }
+
+    fn quote(self, field: &str) -> Cow<'_, str> {
+        match self {
+            Self::Comma => csv_field(field),
+            Self::Tab => tsv_field(field),
+        }
+    }
+}
+
+/// Quote a field per RFC 4180 if it contains a separator, quote or line break
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Escape characters that would break the line and field structure of TSV
+fn tsv_field(field: &str) -> Cow<'_, str> {
+    if field.contains(['\t', '\r', '\n', '\\']) {
+        Cow::Owned(
+            field
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\r', "\\r")
+                .replace('\n', "\\n"),
+        )
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+fn plain_percentage(percentage: Option<f32>) -> String {
+    percentage.map_or_else(String::new, |p| format!("{:.1}", p))
+}
+
+/// Uncolored value of a column, None for purely graphical columns
+pub fn plain_value(
+    mnt: &Mount,
+    column: &ColumnType,
+    delimiter: &NumberFormat,
+    no_aliases: bool,
+) -> Option<String> {
+    Some(match column {
+        ColumnType::Filesystem if no_aliases => mnt.fsname(),
+        ColumnType::Filesystem => mnt.fsname_aliased(),
+        ColumnType::Type => mnt.mnt_type.clone(),
+        ColumnType::Bar | ColumnType::InodeBar => return None,
+        ColumnType::Used => mnt.used_formatted(delimiter),
+        ColumnType::UsedPercentage => plain_percentage(mnt.used_percentage()),
+        ColumnType::Available => mnt.available_formatted(delimiter),
+        ColumnType::AvailablePercentage => plain_percentage(mnt.available_percentage()),
+        ColumnType::Reserved => mnt.reserved_formatted(delimiter),
+        ColumnType::Free => mnt.free_formatted(delimiter),
+        ColumnType::Capacity => mnt.capacity_formatted(delimiter),
+        ColumnType::MountedOn => mnt.dirs_formatted(),
+        ColumnType::Delta => mnt.delta_formatted(delimiter),
+        ColumnType::Rate => mnt.rate_formatted(delimiter),
+        ColumnType::TimeToFull => mnt.time_to_full_formatted(),
+        ColumnType::Host => mnt.host_formatted(),
+        ColumnType::Status => mnt.status_formatted(),
+        ColumnType::Inodes => mnt.inodes_formatted(delimiter),
+        ColumnType::InodesUsed => mnt.inodes_used_formatted(delimiter),
+        ColumnType::InodesFree => mnt.inodes_free_formatted(delimiter),
+        ColumnType::InodesUsedPercentage => plain_percentage(mnt.inodes_used_percentage()),
+    })
+}
+
+pub fn write_delimited<W: Write + ?Sized>(
+    w: &mut W,
+    mnts: &[Mount],
+    columns: &[ColumnType],
+    separator: Separator,
+    delimiter: &NumberFormat,
+    inodes_mode: bool,
+    no_aliases: bool,
+) -> io::Result<()> {
+    let columns = columns
+        .iter()
+        .filter(|column| !matches!(column, ColumnType::Bar | ColumnType::InodeBar))
+        .collect::<Vec<_>>();
+    let sep = separator.as_char().to_string();
+
+    let heading = columns
+        .iter()
+        .map(|column| separator.quote(column.label(inodes_mode)))
+        .collect::<Vec<_>>();
+    writeln!(w, "{}", heading.join(&sep))?;
+
+    for mnt in mnts {
+        let line = columns
+            .iter()
+            .filter_map(|column| plain_value(mnt, column, delimiter, no_aliases))
+            .map(|value| separator.quote(&value).into_owned())
+            .collect::<Vec<_>>();
+        writeln!(w, "{}", line.join(&sep))?;
+    }
+    w.flush()
+}
+
+/// Escape a label value per the Prometheus text exposition format
+fn prometheus_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+struct PrometheusMetric {
+    name: &'static str,
+    unit: &'static str,
+    help: &'static str,
+    value: fn(&StatfsInfo) -> u64,
+}
+
+const PROMETHEUS_METRICS: &[PrometheusMetric] = &[
+    PrometheusMetric {
+        name: "dfrs_filesystem_size_bytes",
+        unit: "bytes",
+        help: "Filesystem size in bytes.",
+        value: |stat| stat.blocks * stat.block_size,
+    },
+    PrometheusMetric {
+        name: "dfrs_filesystem_avail_bytes",
+        unit: "bytes",
+        help: "Filesystem space available to non-root users in bytes.",
+        value: |stat| stat.blocks_available * stat.block_size,
+    },
+    PrometheusMetric {
+        name: "dfrs_filesystem_used_bytes",
+        unit: "bytes",
+        help: "Filesystem space used in bytes, not counting the reserved space.",
+        value: |stat| stat.blocks.saturating_sub(stat.blocks_free) * stat.block_size,
+    },
+    PrometheusMetric {
+        name: "dfrs_filesystem_reserved_bytes",
+        unit: "bytes",
+        help: "Filesystem space free but reserved for root in bytes.",
+        value: |stat| stat.blocks_free.saturating_sub(stat.blocks_available) * stat.block_size,
+    },
+    PrometheusMetric {
+        name: "dfrs_filesystem_files",
+        unit: "",
+        help: "Filesystem total file nodes.",
+        value: |stat| stat.files,
+    },
+    PrometheusMetric {
+        name: "dfrs_filesystem_files_free",
+        unit: "",
+        help: "Filesystem total free file nodes.",
+        value: |stat| stat.files_free,
+    },
+];
+
+/// Write mount usage as Prometheus text format, or as OpenMetrics if
+/// `openmetrics` is set. Mounts without statfs data are left out.
+///
+/// A series must not repeat, so of mounts with the same labels, like a file
+/// system mounted over another on the same device, only the last one is
+/// written, which is the one the kernel resolves the mount point to.
+pub fn write_prometheus<W: Write + ?Sized>(
+    w: &mut W,
+    mnts: &[Mount],
+    openmetrics: bool,
+) -> io::Result<()> {
+    let labels = |mnt: &Mount| {
+        (
+            mnt.mnt_fsname.clone(),
+            mnt.mnt_type.clone(),
+            mnt.mnt_dir.clone(),
+        )
+    };
+    let last = mnts
+        .iter()
+        .enumerate()
+        .map(|(idx, mnt)| (labels(mnt), idx))
+        .collect::<HashMap<_, _>>();
+    let samples = mnts
+        .iter()
+        .enumerate()
+        .filter(|(idx, mnt)| last[&labels(mnt)] == *idx)
+        .filter_map(|(_, mnt)| Some((mnt, mnt.statfs.as_ref()?)))
+        .collect::<Vec<_>>();
+
+    for PrometheusMetric {
+        name,
+        unit,
+        help,
+        value,
+    } in PROMETHEUS_METRICS
+    {
+        writeln!(w, "# HELP {} {}", name, help)?;
+        writeln!(w, "# TYPE {} gauge", name)?;
+        if openmetrics && !unit.is_empty() {
+            writeln!(w, "# UNIT {} {}", name, unit)?;
+        }
+        for (mnt, stat) in &samples {
+            writeln!(
+                w,
+                "{}{{device=\"{}\",device_aliased=\"{}\",fstype=\"{}\",mountpoint=\"{}\"}} {}",
+                name,
+                prometheus_label(&mnt.fsname()),
+                prometheus_label(&mnt.fsname_aliased()),
+                prometheus_label(&mnt.mnt_type),
+                prometheus_label(&mnt.mnt_dir),
+                value(stat)
+            )?;
+        }
+    }
+    if openmetrics {
+        writeln!(w, "# EOF")?;
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfrs::util::Scale;
+
+    fn mount(dir: &str) -> Mount {
+        let mut mnt = Mount::named("/dev/mapper/vg0-data".into());
+        mnt.mnt_dir = dir.to_string();
+        mnt.mnt_type = "ext4".to_string();
+        mnt.capacity = 2048;
+        mnt.available = 512;
+        mnt.used = 1536;
+        mnt
+    }
+
+    fn render(mnts: &[Mount], separator: Separator, delimiter: &NumberFormat) -> String {
+        let columns = [
+            ColumnType::Filesystem,
+            ColumnType::Bar,
+            ColumnType::UsedPercentage,
+            ColumnType::Capacity,
+            ColumnType::MountedOn,
+        ];
+        let mut out = Vec::new();
+        write_delimited(&mut out, mnts, &columns, separator, delimiter, false, false).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn prometheus_label_escaping() {
+        assert_eq!(prometheus_label("/srv/plain"), "/srv/plain");
+        assert_eq!(
+            prometheus_label("/srv/\"a\"\\b\nc"),
+            "/srv/\\\"a\\\"\\\\b\\nc"
+        );
+    }
+
+    #[test]
+    fn write_prometheus_metrics() {
+        let mut mnt = mount("/srv/data");
+        mnt.statfs = Some(StatfsInfo {
+            block_size: 4096,
+            blocks: 100,
+            blocks_free: 30,
+            blocks_available: 20,
+            files: 64,
+            files_free: 16,
+        });
+        let mut out = Vec::new();
+        write_prometheus(&mut out, &[mnt, mount("/srv/stale")], false).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let labels = r#"{device="/dev/mapper/vg0-data",device_aliased="/dev/vg0/data",fstype="ext4",mountpoint="/srv/data"}"#;
+        assert!(out.contains("# HELP dfrs_filesystem_size_bytes Filesystem size in bytes.\n"));
+        assert!(out.contains("# TYPE dfrs_filesystem_size_bytes gauge\n"));
+        assert!(out.contains(&format!("dfrs_filesystem_size_bytes{} 409600\n", labels)));
+        assert!(out.contains(&format!("dfrs_filesystem_avail_bytes{} 81920\n", labels)));
+        assert!(out.contains(&format!("dfrs_filesystem_used_bytes{} 286720\n", labels)));
+        assert!(out.contains(&format!("dfrs_filesystem_reserved_bytes{} 40960\n", labels)));
+        assert!(out.contains(&format!("dfrs_filesystem_files{} 64\n", labels)));
+        assert!(out.contains(&format!("dfrs_filesystem_files_free{} 16\n", labels)));
+        assert!(!out.contains("/srv/stale"));
+        assert!(!out.contains("# UNIT"));
+        assert!(!out.contains("# EOF"));
+    }
+
+    #[test]
+    fn write_prometheus_dedupes_labels() {
+        let stat = |blocks| StatfsInfo {
+            block_size: 1,
+            blocks,
+            ..StatfsInfo::default()
+        };
+        let mut covered = mount("/srv/data");
+        covered.statfs = Some(stat(100));
+        let mut other = mount("/srv/other");
+        other.statfs = Some(stat(300));
+        let mut top = mount("/srv/data");
+        top.statfs = Some(stat(200));
+        let mut out = Vec::new();
+        write_prometheus(&mut out, &[covered, other, top], false).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let sizes = out
+            .lines()
+            .filter(|line| line.starts_with("dfrs_filesystem_size_bytes{"))
+            .map(|line| line.rsplit(' ').next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, ["300", "200"]);
+    }
+
+    #[test]
+    fn write_openmetrics_eof() {
+        let mut out = Vec::new();
+        write_prometheus(&mut out, &[], true).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("# UNIT dfrs_filesystem_size_bytes bytes\n"));
+        assert!(out.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn csv_field_quoting() {
+        assert_eq!(csv_field("/srv/data"), "/srv/data");
+        assert_eq!(csv_field("/srv/a,b"), "\"/srv/a,b\"");
+        assert_eq!(csv_field("/srv/\"q\""), "\"/srv/\"\"q\"\"\"");
+        assert_eq!(csv_field("/srv/a\nb"), "\"/srv/a\nb\"");
+    }
+
+    #[test]
+    fn tsv_field_escaping() {
+        assert_eq!(tsv_field("/srv/a,b"), "/srv/a,b");
+        assert_eq!(tsv_field("/srv/a\tb\\c\nd"), "/srv/a\\tb\\\\c\\nd");
+    }
+
+    #[test]
+    fn write_csv_skips_bar() {
+        let out = render(
+            &[mount("/srv/a,b")],
+            Separator::Comma,
+            &NumberFormat::new(Scale::Base2),
+        );
+        assert_eq!(
+            out,
+            "Filesystem,Used%,Size,Mounted on\n/dev/vg0/data,75.0,2.0K,\"/srv/a,b\"\n"
+        );
+    }
+
+    #[test]
+    fn write_tsv_raw_numbers() {
+        let out = render(
+            &[mount("/srv/data")],
+            Separator::Tab,
+            &NumberFormat::new(Scale::Raw),
+        );
+        assert_eq!(
+            out,
+            "Filesystem\tUsed%\tSize\tMounted on\n/dev/vg0/data\t75.0\t2048\t/srv/data\n"
+        );
+    }
+}
@@ -0,0 +1,785 @@
+use crate::column::ColumnType;
+use crate::errors::{Error, Result};
+use crate::expr::Expr;
+use crate::filter::{DisplayFilter, MountFilter, Rejection};
+use crate::mount::{parse_mounts, Mount, Status};
+#[cfg(feature = "serde")]
+use crate::snapshot::Snapshot;
+use crate::statfs;
+use crate::util;
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::fs::File;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub type MountCmp = fn(&Mount, &Mount) -> Ordering;
+
+/// The rule that shows or hides a mount
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule {
+    /// The display filter level, with the file system name pattern the
+    /// mount matches if any
+    DisplayFilter(DisplayFilter, Option<&'static str>),
+    /// Selected by type or device regardless of the display filter
+    Selected,
+    /// Hidden by the type, mount point or device filters
+    MountFilter(Rejection),
+    /// Hidden for not being local
+    LocalOnly,
+    /// Hidden for its usage having been queried while only failures are kept
+    Statfs,
+    /// Hidden for not matching the predicate
+    Predicate,
+    /// Merged into another mount of the same file system
+    Duplicate,
+    /// Hidden for not being the mount of any of the paths
+    Path,
+    /// Hidden for not being among the fullest mounts
+    Top,
+}
+
+impl Rule {
+    /// Short identifier of the rule
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::DisplayFilter(..) => "display_filter",
+            Self::Selected => "selected",
+            Self::MountFilter(Rejection::Type) => "type",
+            Self::MountFilter(Rejection::ExcludeType) => "exclude_type",
+            Self::MountFilter(Rejection::IncludeMount) => "include_mount",
+            Self::MountFilter(Rejection::ExcludeMount) => "exclude_mount",
+            Self::MountFilter(Rejection::Device) => "device",
+            Self::LocalOnly => "local",
+            Self::Statfs => "statfs",
+            Self::Predicate => "where",
+            Self::Duplicate => "duplicate",
+            Self::Path => "path",
+            Self::Top => "top",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DisplayFilter(level, Some(pattern)) => {
+                write!(f, "display filter {}: name matches {}", level, pattern)
+            }
+            Self::DisplayFilter(level, None) => write!(
+                f,
+                "display filter {}: name matches none of {}",
+                level,
+                level.get_mnt_fsname_filter().join(", ")
+            ),
+            Self::Selected => f.write_str("selected by --type or --device"),
+            Self::MountFilter(Rejection::Type) => f.write_str("type not selected by --type"),
+            Self::MountFilter(Rejection::ExcludeType) => {
+                f.write_str("type excluded by --exclude-type")
+            }
+            Self::MountFilter(Rejection::IncludeMount) => {
+                f.write_str("mount point not matched by --include-mount")
+            }
+            Self::MountFilter(Rejection::ExcludeMount) => {
+                f.write_str("mount point excluded by --exclude-mount")
+            }
+            Self::MountFilter(Rejection::Device) => f.write_str("name not matched by --device"),
+            Self::LocalOnly => f.write_str("not local"),
+            Self::Statfs => f.write_str("usage queried, --errors only shows failures"),
+            Self::Predicate => f.write_str("not matched by --where"),
+            Self::Duplicate => f.write_str("same file system as another mount"),
+            Self::Path => f.write_str("not the file system of any given file"),
+            Self::Top => f.write_str("not among the --top fullest"),
+        }
+    }
+}
+
+/// A mount and its position in the mount table, to tell apart mounts
+/// stacked on the same directory
+#[derive(Debug, Clone)]
+struct Indexed(usize, Mount);
+
+impl AsRef<Mount> for Indexed {
+    fn as_ref(&self) -> &Mount {
+        &self.1
+    }
+}
+
+impl AsMut<Mount> for Indexed {
+    fn as_mut(&mut self) -> &mut Mount {
+        &mut self.1
+    }
+}
+
+/// Why a mount is shown or hidden.
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub mount: Mount,
+    pub shown: bool,
+    pub rule: Rule,
+}
+
+/// Builder to select mounts from a mount table and query their usage.
+///
+/// ```no_run
+/// use dfrs::filter::DisplayFilter;
+/// use dfrs::query::MountQuery;
+///
+/// let mnts = MountQuery::new()
+///     .display_filter(DisplayFilter::More)
+///     .local_only(true)
+///     .run()?;
+/// # Ok::<(), dfrs::errors::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct MountQuery {
+    mount_table: PathBuf,
+    display_filter: DisplayFilter,
+    mount_filter: MountFilter,
+    local_only: bool,
+    inodes: bool,
+    reserved_as_used: bool,
+    paths: Option<Vec<PathBuf>>,
+    sort: Option<MountCmp>,
+    sort_columns: Option<Vec<ColumnType>>,
+    reverse: bool,
+    top: Option<usize>,
+    predicate: Option<Expr>,
+    errors_only: bool,
+    dedupe: bool,
+    workers: usize,
+    timeout: Option<Duration>,
+    snapshot: Option<PathBuf>,
+}
+
+impl Default for MountQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MountQuery {
+    pub fn new() -> Self {
+        Self {
+            mount_table: PathBuf::from("/proc/self/mountinfo"),
+            display_filter: DisplayFilter::Minimal,
+            mount_filter: MountFilter::default(),
+            local_only: false,
+            inodes: false,
+            reserved_as_used: false,
+            paths: None,
+            sort: Some(util::cmp_by_capacity_and_dir_name),
+            sort_columns: None,
+            reverse: false,
+            top: None,
+            predicate: None,
+            errors_only: false,
+            dedupe: false,
+            workers: statfs::DEFAULT_WORKERS,
+            timeout: Some(statfs::DEFAULT_TIMEOUT),
+            snapshot: None,
+        }
+    }
+
+    /// File to read the mount table from, in mounts or mountinfo format
+    pub fn mount_table<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.mount_table = path.into();
+        self
+    }
+
+    pub const fn display_filter(mut self, filter: DisplayFilter) -> Self {
+        self.display_filter = filter;
+        self
+    }
+
+    /// Select mounts by type, mount point and device. Selecting by type or
+    /// device bypasses the display filter.
+    pub fn mount_filter(mut self, filter: MountFilter) -> Self {
+        self.mount_filter = filter;
+        self
+    }
+
+    pub const fn local_only(mut self, local_only: bool) -> Self {
+        self.local_only = local_only;
+        self
+    }
+
+    /// Report inode instead of block usage
+    pub const fn inodes(mut self, inodes: bool) -> Self {
+        self.inodes = inodes;
+        self
+    }
+
+    /// Count the space reserved for the superuser as used, making
+    /// percentages relative to the total size instead of GNU df's
+    /// `used / (used + available)`
+    pub const fn reserved_as_used(mut self, reserved_as_used: bool) -> Self {
+        self.reserved_as_used = reserved_as_used;
+        self
+    }
+
+    /// Only report the best matching mount of each path, in the given order
+    pub fn paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.paths = Some(paths.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Order of the result, ignored when paths are given
+    pub fn sort_by(mut self, cmp: MountCmp) -> Self {
+        self.sort = Some(cmp);
+        self
+    }
+
+    /// Order the result by the raw values of the columns, later columns
+    /// break ties. Unlike [`sort_by`](Self::sort_by) this also orders the
+    /// mounts of the paths.
+    pub fn sort_by_columns<I>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = ColumnType>,
+    {
+        self.sort_columns = Some(columns.into_iter().collect());
+        self
+    }
+
+    /// Reverse the order of the result
+    pub const fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Only keep the given number of fullest mounts, fullest first unless
+    /// sorted by columns
+    pub const fn top(mut self, top: Option<usize>) -> Self {
+        self.top = top;
+        self
+    }
+
+    /// Only keep mounts matching the expression, checked against their usage
+    pub fn predicate(mut self, expr: Option<Expr>) -> Self {
+        self.predicate = expr;
+        self
+    }
+
+    /// Only keep mounts whose usage could not be queried
+    pub const fn errors_only(mut self, errors_only: bool) -> Self {
+        self.errors_only = errors_only;
+        self
+    }
+
+    /// Collapse mounts of the same file system into the first one
+    pub const fn dedupe(mut self, dedupe: bool) -> Self {
+        self.dedupe = dedupe;
+        self
+    }
+
+    /// Number of file systems to query at once
+    pub const fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Time to wait for the usage of a single file system before marking it
+    /// stale, or `None` to wait indefinitely
+    pub const fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Keep the order of the mount table
+    pub fn unsorted(mut self) -> Self {
+        self.sort = None;
+        self
+    }
+
+    /// Replay the mounts and statfs numbers of a snapshot written by
+    /// `--output json` instead of querying the local system. Paths are
+    /// matched against the snapshot as given.
+    #[cfg(feature = "serde")]
+    pub fn snapshot<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.snapshot = Some(path.into());
+        self
+    }
+
+    /// Read the mount table and query the usage of the selected mounts
+    pub fn run(&self) -> Result<Vec<Mount>> {
+        let mut mnts = self.filter(self.load()?);
+        self.update_usage(&mut mnts);
+        self.select(mnts)
+    }
+
+    /// Every mount of the mount table with the rule that shows or hides it,
+    /// in the order of the mount table. The usage of hidden mounts is
+    /// queried as well.
+    pub fn explain(&self) -> Result<Vec<Explanation>> {
+        let mut mnts = self.load()?;
+        self.update_usage(&mut mnts);
+        let mut explanations = Vec::new();
+        let mut kept = Vec::new();
+        for (idx, mnt) in mnts.into_iter().enumerate() {
+            let (shown, rule) = self.filter_rule(&mnt);
+            if shown {
+                kept.push(Indexed(idx, mnt.clone()));
+            }
+            explanations.push(Explanation {
+                mount: mnt,
+                shown,
+                rule,
+            });
+        }
+
+        let selected = self.select(kept)?;
+        for (idx, explanation) in explanations.iter_mut().enumerate() {
+            let mnt = &explanation.mount;
+            if !explanation.shown || selected.iter().any(|s| s.0 == idx) {
+                continue;
+            }
+            explanation.shown = false;
+            explanation.rule = if self.errors_only && mnt.status.is_ok() {
+                Rule::Statfs
+            } else if self.predicate.as_ref().is_some_and(|e| !e.matches(mnt)) {
+                Rule::Predicate
+            } else if selected
+                .iter()
+                .any(|s| s.1.other_dirs.contains(&mnt.mnt_dir) && s.1.fs_key() == mnt.fs_key())
+            {
+                Rule::Duplicate
+            } else if self.paths.is_some() {
+                Rule::Path
+            } else {
+                Rule::Top
+            };
+        }
+        Ok(explanations)
+    }
+
+    /// The unfiltered mounts of the mount table or snapshot
+    fn load(&self) -> Result<Vec<Mount>> {
+        #[cfg(feature = "serde")]
+        if let Some(path) = &self.snapshot {
+            return Ok(Snapshot::load(path)?.mounts());
+        }
+
+        let f = File::open(&self.mount_table).map_err(|source| Error::OpenMountTable {
+            path: self.mount_table.clone(),
+            source,
+        })?;
+        parse_mounts(f)
+    }
+
+    /// Query the usage of the mounts in parallel, or take it from the
+    /// statfs numbers recorded in the snapshot
+    fn update_usage(&self, mnts: &mut [Mount]) {
+        if self.snapshot.is_some() {
+            for mnt in mnts.iter_mut().filter(|mnt| mnt.statfs.is_some()) {
+                mnt.compute_usage(self.inodes);
+            }
+        } else {
+            let dirs = mnts
+                .iter()
+                .map(|mnt| mnt.mnt_dir.clone())
+                .collect::<Vec<_>>();
+            let results = statfs::query_all(&dirs, self.workers, self.timeout);
+            for (mnt, result) in mnts.iter_mut().zip(results) {
+                (mnt.statfs, mnt.status) = match result {
+                    Ok(usage) => {
+                        mnt.mnt_dev = mnt.mnt_dev.or(usage.dev);
+                        (Some(usage.statfs), Status::Ok)
+                    }
+                    Err(status) => (None, status),
+                };
+                mnt.compute_usage(self.inodes);
+            }
+        }
+        if self.reserved_as_used {
+            mnts.iter_mut().for_each(Mount::count_reserved_as_used);
+        }
+    }
+
+    /// Apply the display, mount and local-only filters to an already parsed
+    /// mount table
+    pub fn filter(&self, mut mnts: Vec<Mount>) -> Vec<Mount> {
+        mnts.retain(|mnt| self.filter_rule(mnt).0);
+        mnts
+    }
+
+    /// Whether the filters keep the mount and the rule deciding it
+    fn filter_rule(&self, mnt: &Mount) -> (bool, Rule) {
+        let kept_by = if self.mount_filter.selects() {
+            Rule::Selected
+        } else {
+            let pattern = self.display_filter.matching_pattern(mnt);
+            let rule = Rule::DisplayFilter(self.display_filter.clone(), pattern);
+            if pattern.is_none() {
+                return (false, rule);
+            }
+            rule
+        };
+        if let Some(rejection) = self.mount_filter.rejection(mnt) {
+            return (false, Rule::MountFilter(rejection));
+        }
+        if self.local_only && !mnt.is_local() {
+            return (false, Rule::LocalOnly);
+        }
+        (true, kept_by)
+    }
+
+    /// Resolve the paths to their mounts, or sort all mounts
+    pub fn select<T>(&self, mut mnts: Vec<T>) -> Result<Vec<T>>
+    where
+        T: AsRef<Mount> + AsMut<Mount> + Clone,
+    {
+        if let Some(paths) = &self.paths {
+            mnts = self.select_paths(paths, &mnts)?;
+        } else if let (Some(cmp), None) = (self.sort, &self.sort_columns) {
+            mnts.sort_by(|a, b| cmp(a.as_ref(), b.as_ref()));
+        }
+        if self.errors_only {
+            mnts.retain(|mnt| !mnt.as_ref().status.is_ok());
+        }
+        if let Some(expr) = &self.predicate {
+            mnts.retain(|mnt| expr.matches(mnt.as_ref()));
+        }
+        if self.dedupe {
+            mnts = util::dedupe(mnts);
+        }
+
+        if let Some(top) = self.top {
+            mnts.sort_by(|a, b| ColumnType::UsedPercentage.cmp_mounts(b.as_ref(), a.as_ref()));
+            mnts.truncate(top);
+        }
+        if let Some(columns) = &self.sort_columns {
+            mnts.sort_by(|a, b| {
+                columns.iter().fold(Ordering::Equal, |ord, column| {
+                    ord.then_with(|| column.cmp_mounts(a.as_ref(), b.as_ref()))
+                })
+            });
+        }
+        if self.reverse {
+            mnts.reverse();
+        }
+        Ok(mnts)
+    }
+
+    /// The best matching mount of each path, in the order of the paths
+    fn select_paths<T: AsRef<Mount> + Clone>(
+        &self,
+        paths: &[PathBuf],
+        mnts: &[T],
+    ) -> Result<Vec<T>> {
+        let mut out = Vec::new();
+        for path in paths {
+            let path = if self.snapshot.is_some() {
+                path.clone()
+            } else {
+                path.canonicalize().map_err(|source| Error::Path {
+                    path: path.clone(),
+                    source,
+                })?
+            };
+            if let Some(mnt) = util::get_best_mount_match(&path, mnts) {
+                out.push(mnt.clone());
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(fsname: &str, dir: &str, mnt_type: &str, capacity: u64) -> Mount {
+        let mut mnt = Mount::named(fsname.into());
+        mnt.mnt_dir = dir.to_string();
+        mnt.mnt_type = mnt_type.to_string();
+        mnt.capacity = capacity;
+        mnt
+    }
+
+    #[test]
+    fn filter_display_minimal() {
+        let mnts = vec![
+            mount("/dev/sda1", "/", "ext4", 1),
+            mount("tmpfs", "/tmp", "tmpfs", 1),
+            mount("server:/export", "/mnt/nfs", "nfs4", 1),
+        ];
+        let mnts = MountQuery::new().filter(mnts);
+        assert_eq!(mnts.len(), 1);
+        assert_eq!(mnts[0].mnt_dir, "/");
+    }
+
+    #[test]
+    fn filter_local_only() {
+        let mnts = vec![
+            mount("/dev/sda1", "/", "ext4", 1),
+            mount("server:/export", "/mnt/nfs", "nfs4", 1),
+        ];
+        let mnts = MountQuery::new()
+            .display_filter(DisplayFilter::All)
+            .local_only(true)
+            .filter(mnts);
+        assert_eq!(mnts.len(), 1);
+        assert_eq!(mnts[0].mnt_dir, "/");
+    }
+
+    #[test]
+    fn filter_type_bypasses_display_filter() {
+        let mnts = vec![
+            mount("/dev/sda1", "/", "ext4", 1),
+            mount("tmpfs", "/tmp", "tmpfs", 1),
+        ];
+        let mnts = MountQuery::new()
+            .mount_filter(MountFilter {
+                types: vec!["tmpfs".to_string()],
+                ..Default::default()
+            })
+            .filter(mnts);
+        assert_eq!(mnts.len(), 1);
+        assert_eq!(mnts[0].mnt_dir, "/tmp");
+    }
+
+    #[test]
+    fn select_sorted() {
+        let mnts = vec![
+            mount("proc", "/proc", "proc", 0),
+            mount("/dev/sda2", "/home", "ext4", 1),
+            mount("/dev/sda1", "/", "ext4", 1),
+        ];
+        let mnts = MountQuery::new().select(mnts).unwrap();
+        let dirs = mnts.iter().map(|m| m.mnt_dir.as_str()).collect::<Vec<_>>();
+        assert_eq!(dirs, vec!["/", "/home", "/proc"]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn run_snapshot() {
+        use crate::mount::StatfsInfo;
+        use crate::snapshot::Snapshot;
+        use std::fs;
+
+        let mut root = mount("/dev/sda1", "/", "ext4", 0);
+        root.statfs = Some(StatfsInfo {
+            block_size: 1024,
+            blocks: 100,
+            blocks_free: 50,
+            blocks_available: 40,
+            files: 10,
+            files_free: 4,
+        });
+        let mnts = [root, mount("proc", "/proc", "proc", 0)];
+        let path = std::env::temp_dir().join(format!("dfrs-query-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            serde_json::to_vec(&Snapshot::capture(&mnts, None, false)).unwrap(),
+        )
+        .unwrap();
+
+        let query = MountQuery::new().snapshot(&path);
+        let result = query.run().unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].capacity, 102_400);
+        assert_eq!(result[0].available, 40_960);
+        assert_eq!(result[0].reserved, 10_240);
+        assert_eq!(result[0].used, 51_200);
+        assert_eq!(result[0].used_percentage(), Some(55.555557));
+
+        let result = query.clone().reserved_as_used(true).run().unwrap();
+        assert_eq!(result[0].reserved, 0);
+        assert_eq!(result[0].used_percentage(), Some(60.0));
+
+        let result = query.inodes(true).paths(["/no/such/dir"]).run().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result[0].mnt_dir, "/");
+        assert_eq!(result[0].capacity, 10);
+    }
+
+    #[test]
+    fn explain_rules() {
+        let path = std::env::temp_dir().join(format!("dfrs-mounts-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "/dev/sda1 / ext4 rw 0 0\n\
+             proc /proc proc rw 0 0\n\
+             /dev/sdb1 /srv xfs rw 0 0\n\
+             /dev/sdc1 /no/such/dir ext4 rw 0 0\n",
+        )
+        .unwrap();
+        let query = MountQuery::new()
+            .mount_table(&path)
+            .mount_filter(MountFilter {
+                exclude_types: vec!["xfs".into()],
+                ..Default::default()
+            })
+            .predicate(Some("dir != '/no/such/dir'".parse().unwrap()));
+        let explanations = query.explain().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let rules = explanations
+            .iter()
+            .map(|e| (e.mount.mnt_dir.as_str(), e.shown, e.rule.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rules,
+            [
+                (
+                    "/",
+                    true,
+                    Rule::DisplayFilter(DisplayFilter::Minimal, Some("/dev*"))
+                ),
+                (
+                    "/proc",
+                    false,
+                    Rule::DisplayFilter(DisplayFilter::Minimal, None)
+                ),
+                ("/srv", false, Rule::MountFilter(Rejection::ExcludeType)),
+                ("/no/such/dir", false, Rule::Predicate),
+            ]
+        );
+        assert_eq!(explanations[0].mount.status, Status::Ok);
+        assert_eq!(
+            explanations[3].mount.status,
+            Status::Failed(nix::errno::Errno::ENOENT)
+        );
+        assert_eq!(
+            explanations[1].rule.to_string(),
+            "display filter minimal: name matches none of /dev*, storage"
+        );
+    }
+
+    #[test]
+    fn explain_errors_and_stacked_mounts() {
+        let path = std::env::temp_dir().join(format!("dfrs-mounts-stacked-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "/dev/sda1 / ext4 rw 0 0\n\
+             /dev/sda1 / ext4 rw 0 0\n\
+             /dev/sdc1 /no/such/dir ext4 rw 0 0\n",
+        )
+        .unwrap();
+        let query = MountQuery::new().mount_table(&path);
+        let stacked = query.clone().top(Some(1)).explain().unwrap();
+        let errors = query.errors_only(true).explain().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let rules = |explanations: &[Explanation]| {
+            explanations
+                .iter()
+                .map(|e| (e.shown, e.rule.name()))
+                .collect::<Vec<_>>()
+        };
+        // the second of the stacked mounts is not shown along with the first
+        assert_eq!(
+            rules(&stacked),
+            [(true, "display_filter"), (false, "top"), (false, "top")]
+        );
+        assert_eq!(
+            rules(&errors),
+            [
+                (false, "statfs"),
+                (false, "statfs"),
+                (true, "display_filter")
+            ]
+        );
+    }
+
+    #[test]
+    fn select_unsorted() {
+        let mnts = vec![
+            mount("proc", "/proc", "proc", 0),
+            mount("/dev/sda1", "/", "ext4", 1),
+        ];
+        let mnts = MountQuery::new().unsorted().select(mnts).unwrap();
+        assert_eq!(mnts[0].mnt_dir, "/proc");
+    }
+
+    fn used(dir: &str, capacity: u64, used: u64) -> Mount {
+        let mut mnt = mount("/dev/sda1", dir, "ext4", capacity);
+        mnt.used = used;
+        mnt.available = capacity - used;
+        mnt
+    }
+
+    fn dirs(mnts: &[Mount]) -> Vec<&str> {
+        mnts.iter().map(|m| m.mnt_dir.as_str()).collect()
+    }
+
+    #[test]
+    fn select_sort_by_columns() {
+        let mnts = vec![
+            used("/a", 100, 50),
+            used("/b", 1000, 900),
+            used("/c", 10, 5),
+        ];
+        let query =
+            MountQuery::new().sort_by_columns([ColumnType::UsedPercentage, ColumnType::Capacity]);
+        assert_eq!(
+            dirs(&query.select(mnts.clone()).unwrap()),
+            ["/c", "/a", "/b"]
+        );
+
+        let query = MountQuery::new()
+            .sort_by_columns([ColumnType::Used])
+            .reverse(true);
+        assert_eq!(dirs(&query.select(mnts).unwrap()), ["/b", "/a", "/c"]);
+    }
+
+    #[test]
+    fn select_top() {
+        let mnts = vec![
+            mount("proc", "/proc", "proc", 0),
+            used("/a", 100, 50),
+            used("/b", 100, 90),
+            used("/c", 100, 70),
+        ];
+        let result = MountQuery::new().top(Some(2)).select(mnts.clone()).unwrap();
+        assert_eq!(dirs(&result), ["/b", "/c"]);
+
+        let result = MountQuery::new()
+            .top(Some(2))
+            .sort_by_columns([ColumnType::MountedOn])
+            .select(mnts)
+            .unwrap();
+        assert_eq!(dirs(&result), ["/b", "/c"]);
+    }
+
+    #[test]
+    fn select_predicate() {
+        let mnts = vec![
+            mount("proc", "/proc", "proc", 0),
+            used("/a", 100, 50),
+            used("/b", 100, 90),
+            used("/c", 100, 70),
+        ];
+        let query = MountQuery::new()
+            .predicate(Some("used_percentage >= 70%".parse().unwrap()))
+            .top(Some(1));
+        assert_eq!(dirs(&query.select(mnts.clone()).unwrap()), ["/b"]);
+
+        let query = MountQuery::new().predicate(Some("!dir ~ '^/[ab]$'".parse().unwrap()));
+        assert_eq!(dirs(&query.select(mnts).unwrap()), ["/c", "/proc"]);
+    }
+
+    #[test]
+    fn select_paths_keep_order_unless_sorted() {
+        let mnts = vec![used("/", 100, 90), used("/tmp", 100, 10)];
+        let query = MountQuery::new().paths(["/tmp", "/"]);
+        assert_eq!(dirs(&query.select(mnts.clone()).unwrap()), ["/tmp", "/"]);
+
+        let query = query.sort_by_columns([ColumnType::MountedOn]);
+        assert_eq!(dirs(&query.select(mnts).unwrap()), ["/", "/tmp"]);
+    }
+
+    #[test]
+    fn select_paths_missing() {
+        let mnts = vec![mount("/dev/sda1", "/", "ext4", 1)];
+        let result = MountQuery::new()
+            .paths(["/nonexistent/dfrs/path"])
+            .select(mnts);
+        assert!(matches!(result, Err(Error::Path { .. })));
+    }
+}
@@ -0,0 +1,472 @@
+use crate::errors::{Error, Result};
+use crate::mount::{Mount, StatfsInfo, Status};
+use crate::util::unix_time;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+/// Version of the JSON document layout, bumped on incompatible changes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Serializable view of the queried mounts, as written by `--output json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub schema_version: u32,
+    pub hostname: Option<String>,
+    pub timestamp: u64,
+    pub inodes: bool,
+    pub mounts: Vec<MountRecord>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<MountRecord>,
+}
+
+impl Snapshot {
+    /// Capture the given mounts together with the local hostname and time
+    pub fn capture(mnts: &[Mount], total: Option<&Mount>, inodes: bool) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            hostname: nix::unistd::gethostname()
+                .ok()
+                .and_then(|name| name.into_string().ok()),
+            timestamp: unix_time(),
+            inodes,
+            mounts: mnts.iter().map(MountRecord::from).collect(),
+            total: total.map(MountRecord::from),
+        }
+    }
+
+    /// Read a snapshot previously written by `--output json`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read(path).map_err(|source| Error::ReadSnapshot {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let snapshot =
+            serde_json::from_slice::<Self>(&content).map_err(|source| Error::InvalidSnapshot {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        if snapshot.schema_version > SCHEMA_VERSION {
+            return Err(Error::UnsupportedSnapshot(snapshot.schema_version));
+        }
+        Ok(snapshot)
+    }
+
+    /// The captured mounts, without the total, belonging to the captured host
+    pub fn mounts(&self) -> Vec<Mount> {
+        self.mounts
+            .iter()
+            .map(|record| {
+                let mut mnt = Mount::from(record);
+                mnt.host = mnt.host.or_else(|| self.hostname.clone());
+                mnt
+            })
+            .collect()
+    }
+}
+
+/// A single mount with its usage, values are raw numbers in bytes or inodes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MountRecord {
+    pub fsname: String,
+    pub fsname_aliased: String,
+    pub dir: String,
+    #[serde(rename = "type")]
+    pub mnt_type: String,
+    pub options: String,
+    pub freq: i32,
+    pub passno: i32,
+    pub mount_id: Option<u32>,
+    pub parent_id: Option<u32>,
+    pub major: Option<u32>,
+    pub minor: Option<u32>,
+    pub root: Option<String>,
+    #[serde(default)]
+    pub optional_fields: Vec<String>,
+    pub super_options: Option<String>,
+    pub capacity: u64,
+    /// Space in use, not counting the reserved space
+    pub used: u64,
+    /// Space available to unprivileged users
+    pub available: u64,
+    /// Free space only available to the superuser
+    #[serde(default)]
+    pub reserved: u64,
+    pub used_percentage: Option<f32>,
+    pub available_percentage: Option<f32>,
+    pub statfs: Option<StatfsInfo>,
+    /// Whether querying the usage succeeded, see [`Status::name`]
+    #[serde(default)]
+    pub status: Option<String>,
+    /// Description of the failure to query the usage
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errno: Option<i32>,
+    /// Further mount points of the same file system with `--dedupe`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub other_dirs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+}
+
+impl From<&Mount> for MountRecord {
+    fn from(mnt: &Mount) -> Self {
+        Self {
+            fsname: mnt.fsname(),
+            fsname_aliased: mnt.fsname_aliased(),
+            dir: mnt.mnt_dir.clone(),
+            mnt_type: mnt.mnt_type.clone(),
+            options: mnt.mnt_opts.clone(),
+            freq: mnt.mnt_freq,
+            passno: mnt.mnt_passno,
+            mount_id: mnt.mnt_id,
+            parent_id: mnt.mnt_parent_id,
+            major: mnt.mnt_dev.map(|(major, _)| major),
+            minor: mnt.mnt_dev.map(|(_, minor)| minor),
+            root: mnt.mnt_root.clone(),
+            optional_fields: mnt.mnt_optional_fields.clone(),
+            super_options: mnt.mnt_super_opts.clone(),
+            capacity: mnt.capacity,
+            used: mnt.used,
+            available: mnt.available,
+            reserved: mnt.reserved,
+            used_percentage: mnt.used_percentage(),
+            available_percentage: mnt.available_percentage(),
+            statfs: mnt.statfs,
+            status: Some(mnt.status.name().to_string()),
+            error: (!mnt.status.is_ok()).then(|| mnt.status.to_string()),
+            errno: mnt.status.errno().map(|errno| errno as i32),
+            other_dirs: mnt.other_dirs.clone(),
+            host: mnt.host.clone(),
+        }
+    }
+}
+
+impl From<&MountRecord> for Mount {
+    fn from(record: &MountRecord) -> Self {
+        let mut mnt = Self::named(record.fsname.clone());
+        mnt.mnt_dir = record.dir.clone();
+        mnt.mnt_type = record.mnt_type.clone();
+        mnt.mnt_opts = record.options.clone();
+        mnt.mnt_freq = record.freq;
+        mnt.mnt_passno = record.passno;
+        mnt.mnt_id = record.mount_id;
+        mnt.mnt_parent_id = record.parent_id;
+        mnt.mnt_dev = record.major.zip(record.minor);
+        mnt.mnt_root = record.root.clone();
+        mnt.mnt_optional_fields = record.optional_fields.clone();
+        mnt.mnt_super_opts = record.super_options.clone();
+        mnt.capacity = record.capacity;
+        mnt.used = record.used;
+        mnt.available = record.available;
+        mnt.reserved = record.reserved;
+        mnt.statfs = record.statfs;
+        mnt.status = record
+            .status
+            .as_deref()
+            .and_then(|name| Status::from_name(name, record.errno))
+            .unwrap_or_default();
+        mnt.other_dirs = record.other_dirs.clone();
+        mnt.host = record.host.clone();
+        mnt
+    }
+}
+
+/// A single line of `--output ndjson`: one mount record tagged with the
+/// schema version and the host it was captured on.
+#[derive(Debug, Serialize)]
+pub struct NdjsonRecord<'a> {
+    pub schema_version: u32,
+    pub hostname: Option<&'a str>,
+    pub timestamp: u64,
+    pub inodes: bool,
+    #[serde(flatten)]
+    pub mount: &'a MountRecord,
+}
+
+impl Snapshot {
+    pub fn ndjson_records(&self) -> impl Iterator<Item = NdjsonRecord<'_>> {
+        self.mounts
+            .iter()
+            .chain(self.total.iter())
+            .map(|mount| NdjsonRecord {
+                schema_version: self.schema_version,
+                hostname: self.hostname.as_deref(),
+                timestamp: self.timestamp,
+                inodes: self.inodes,
+                mount,
+            })
+    }
+}
+
+fn nullable(kind: &str) -> Value {
+    json!({ "type": [kind, "null"] })
+}
+
+/// JSON Schema describing the `--output json` document. The `ndjson_record`
+/// definition describes every line of `--output ndjson`.
+pub fn json_schema() -> Value {
+    let statfs = json!({
+        "type": "object",
+        "description": "Raw statfs(2) numbers",
+        "required": ["block_size", "blocks", "blocks_free", "blocks_available", "files", "files_free"],
+        "properties": {
+            "block_size": { "type": "integer", "minimum": 0 },
+            "blocks": { "type": "integer", "minimum": 0 },
+            "blocks_free": { "type": "integer", "minimum": 0 },
+            "blocks_available": { "type": "integer", "minimum": 0 },
+            "files": { "type": "integer", "minimum": 0 },
+            "files_free": { "type": "integer", "minimum": 0 }
+        }
+    });
+    let mount = json!({
+        "type": "object",
+        "required": [
+            "fsname", "fsname_aliased", "dir", "type", "options", "freq", "passno",
+            "capacity", "used", "available", "used_percentage", "available_percentage", "statfs"
+        ],
+        "properties": {
+            "fsname": { "type": "string" },
+            "fsname_aliased": { "type": "string" },
+            "dir": { "type": "string" },
+            "type": { "type": "string" },
+            "options": { "type": "string" },
+            "freq": { "type": "integer" },
+            "passno": { "type": "integer" },
+            "mount_id": nullable("integer"),
+            "parent_id": nullable("integer"),
+            "major": nullable("integer"),
+            "minor": nullable("integer"),
+            "root": nullable("string"),
+            "optional_fields": { "type": "array", "items": { "type": "string" } },
+            "super_options": nullable("string"),
+            "capacity": { "type": "integer", "minimum": 0 },
+            "used": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Space in use, not counting the reserved space"
+            },
+            "available": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Space available to unprivileged users"
+            },
+            "reserved": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Free space only available to the superuser"
+            },
+            "used_percentage": {
+                "type": ["number", "null"],
+                "description": "Share of used + available in use, like GNU df"
+            },
+            "available_percentage": nullable("number"),
+            "statfs": { "oneOf": [{ "$ref": "#/$defs/statfs" }, { "type": "null" }] },
+            "status": {
+                "enum": ["ok", "stale", "stale_handle", "io_error", "error"],
+                "description": "Whether querying the usage succeeded, stale if it timed out"
+            },
+            "error": { "type": "string", "description": "Why querying the usage failed" },
+            "errno": { "type": "integer", "description": "Error number of the failure" },
+            "other_dirs": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "Further mount points of the same file system collapsed by --dedupe"
+            },
+            "host": { "type": "string", "description": "Host of the mount when merged from several snapshots" }
+        }
+    });
+    let header = json!({
+        "schema_version": { "const": SCHEMA_VERSION },
+        "hostname": nullable("string"),
+        "timestamp": { "type": "integer", "description": "Seconds since the Unix epoch" },
+        "inodes": { "type": "boolean", "description": "Whether sizes count inodes instead of bytes" }
+    });
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("urn:dfrs:schema:v{}", SCHEMA_VERSION),
+        "title": "dfrs file system usage",
+        "type": "object",
+        "required": ["schema_version", "hostname", "timestamp", "inodes", "mounts"],
+        "properties": {
+            "schema_version": header["schema_version"],
+            "hostname": header["hostname"],
+            "timestamp": header["timestamp"],
+            "inodes": header["inodes"],
+            "mounts": { "type": "array", "items": { "$ref": "#/$defs/mount" } },
+            "total": { "$ref": "#/$defs/mount" }
+        },
+        "$defs": {
+            "statfs": statfs,
+            "mount": mount,
+            "ndjson_record": {
+                "allOf": [
+                    { "$ref": "#/$defs/mount" },
+                    {
+                        "type": "object",
+                        "required": ["schema_version", "hostname", "timestamp", "inodes"],
+                        "properties": header
+                    }
+                ]
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_raw_numbers() {
+        let mut mnt = Mount::named("/dev/mapper/vg0-root".into());
+        mnt.mnt_dir = "/".to_string();
+        mnt.capacity = 1000;
+        mnt.available = 250;
+        mnt.used = 750;
+
+        let snapshot = Snapshot::capture(&[mnt], None, false);
+        let value = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+        assert_eq!(value["mounts"][0]["fsname_aliased"], "/dev/vg0/root");
+        assert_eq!(value["mounts"][0]["type"], "-");
+        assert_eq!(value["mounts"][0]["capacity"], 1000);
+        assert_eq!(value["mounts"][0]["used_percentage"], 75.0);
+        assert!(value.get("total").is_none());
+    }
+
+    #[test]
+    fn snapshot_mounts_roundtrip() {
+        let mut mnt = Mount::named("/dev/sda1".into());
+        mnt.mnt_dir = "/srv".to_string();
+        mnt.mnt_dev = Some((8, 1));
+        mnt.statfs = Some(StatfsInfo {
+            block_size: 4096,
+            blocks: 100,
+            blocks_free: 60,
+            blocks_available: 50,
+            files: 10,
+            files_free: 5,
+        });
+        mnt.compute_usage(false);
+
+        let json = serde_json::to_string(&Snapshot::capture(&[mnt.clone()], None, false)).unwrap();
+        let mounts = serde_json::from_str::<Snapshot>(&json).unwrap().mounts();
+        assert_eq!(mounts.len(), 1);
+        assert_eq!(mounts[0].mnt_dir, mnt.mnt_dir);
+        assert_eq!(mounts[0].mnt_dev, mnt.mnt_dev);
+        assert_eq!(mounts[0].statfs, mnt.statfs);
+        assert_eq!(mounts[0].used, mnt.used);
+    }
+
+    #[test]
+    fn snapshot_status_roundtrip() {
+        let mut mnts = [
+            Mount::named("nfs:/export".into()),
+            Mount::named("/dev/sda1".into()),
+        ];
+        mnts[0].status = Status::Failed(nix::errno::Errno::EACCES);
+        mnts[1].status = Status::StaleHandle;
+
+        let value = serde_json::to_value(Snapshot::capture(&mnts, None, false)).unwrap();
+        assert_eq!(value["mounts"][0]["status"], "error");
+        assert_eq!(value["mounts"][0]["error"], "Permission denied");
+        assert_eq!(value["mounts"][1]["status"], "stale_handle");
+
+        let mounts = serde_json::from_value::<Snapshot>(value).unwrap().mounts();
+        assert_eq!(mounts[0].status, mnts[0].status);
+        assert_eq!(mounts[1].status, mnts[1].status);
+    }
+
+    #[test]
+    fn load_rejects_newer_schema() {
+        let path = std::env::temp_dir().join(format!("dfrs-snapshot-{}.json", std::process::id()));
+        let mut snapshot = Snapshot::capture(&[], None, false);
+        snapshot.schema_version = SCHEMA_VERSION + 1;
+        fs::write(&path, serde_json::to_vec(&snapshot).unwrap()).unwrap();
+        let result = Snapshot::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(Error::UnsupportedSnapshot(_))));
+    }
+
+    #[test]
+    fn ndjson_records_include_total() {
+        let mnt = Mount::named("foo".into());
+        let total = Mount::named("total".into());
+        let snapshot = Snapshot::capture(&[mnt], Some(&total), true);
+
+        let records = snapshot
+            .ndjson_records()
+            .map(|record| serde_json::to_value(record).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["schema_version"], SCHEMA_VERSION);
+        assert_eq!(records[0]["inodes"], true);
+        assert_eq!(records[1]["fsname"], "total");
+    }
+
+    #[test]
+    fn json_schema_requires_mount_fields() {
+        let schema = json_schema();
+        let required = schema["$defs"]["mount"]["required"].as_array().unwrap();
+        let mnt = serde_json::to_value(MountRecord::from(&Mount::named("foo".into()))).unwrap();
+        for field in required {
+            assert!(mnt.get(field.as_str().unwrap()).is_some(), "{}", field);
+        }
+    }
+
+    #[test]
+    fn snapshot_validates_against_json_schema() {
+        let mut mnt = Mount::named("/dev/sda1".into());
+        mnt.mnt_dir = "/srv".to_string();
+        mnt.mnt_dev = Some((8, 1));
+        mnt.statfs = Some(StatfsInfo {
+            block_size: 4096,
+            blocks: 100,
+            blocks_free: 60,
+            blocks_available: 50,
+            files: 10,
+            files_free: 5,
+        });
+        mnt.compute_usage(false);
+        let mut failed = Mount::named("nfs:/export".into());
+        failed.status = Status::Failed(nix::errno::Errno::EACCES);
+        let mnts = [mnt, failed];
+        let total = crate::util::calc_total(&mnts);
+        let snapshot = Snapshot::capture(&mnts, Some(&total), false);
+
+        let schema = json_schema();
+        let document = jsonschema::JSONSchema::compile(&schema).unwrap();
+        let record = jsonschema::JSONSchema::compile(&json!({
+            "$schema": schema["$schema"],
+            "$id": schema["$id"],
+            "$defs": schema["$defs"],
+            "$ref": "#/$defs/ndjson_record"
+        }))
+        .unwrap();
+        let errors = |schema: &jsonschema::JSONSchema, value: &Value| {
+            schema
+                .validate(value)
+                .err()
+                .map(|errors| errors.map(|err| err.to_string()).collect::<Vec<_>>())
+        };
+
+        let mut value = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(errors(&document, &value), None);
+        for line in snapshot.ndjson_records() {
+            let line = serde_json::to_value(line).unwrap();
+            assert_eq!(errors(&record, &line), None);
+        }
+
+        value["mounts"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("available");
+        assert!(!document.is_valid(&value));
+    }
+}
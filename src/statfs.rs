@@ -0,0 +1,325 @@
+//! Parallel statfs(2) queries with a timeout.
+//!
+//! The calls run on a bounded pool of worker threads, so a single
+//! unresponsive file system, like an NFS mount of a dead server, only delays
+//! its own result by the timeout instead of blocking all others.
+
+use crate::mount::{StatfsInfo, Status};
+
+use log::debug;
+use nix::sys::stat;
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Number of statfs calls running at once
+pub const DEFAULT_WORKERS: usize = 8;
+/// Time a single statfs call may take before its mount is considered stale
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+type Jobs = Arc<Mutex<std::vec::IntoIter<(usize, String)>>>;
+type Outstanding = Mutex<Calls>;
+
+static OUTSTANDING: Outstanding = Mutex::new(Calls::new());
+
+/// The running calls by a token of their own, as the same directory may be
+/// queried more than once, with the directory and whether the call timed out
+struct Calls {
+    next: u64,
+    running: BTreeMap<u64, (String, bool)>,
+}
+
+impl Calls {
+    const fn new() -> Self {
+        Self {
+            next: 0,
+            running: BTreeMap::new(),
+        }
+    }
+
+    fn start(&mut self, dir: String) -> u64 {
+        let token = self.next;
+        self.next += 1;
+        self.running.insert(token, (dir, false));
+        token
+    }
+
+    fn time_out(&mut self, token: u64) {
+        if let Some((_, timed_out)) = self.running.get_mut(&token) {
+            *timed_out = true;
+        }
+    }
+
+    fn finish(&mut self, token: u64) {
+        self.running.remove(&token);
+    }
+
+    fn hung(&self, dir: &str) -> bool {
+        self.running
+            .values()
+            .any(|(running, timed_out)| running == dir && *timed_out)
+    }
+}
+
+enum Message<T> {
+    Started(usize, u64, Instant),
+    Done(usize, nix::Result<T>),
+}
+
+/// Usage of a mount and the device number of its file system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub statfs: StatfsInfo,
+    pub dev: Option<(u32, u32)>,
+}
+
+impl Usage {
+    pub fn query(dir: &str) -> nix::Result<Self> {
+        let statfs = StatfsInfo::query(dir)?;
+        let dev = stat::stat(dir)
+            .ok()
+            .map(|st| (stat::major(st.st_dev) as u32, stat::minor(st.st_dev) as u32));
+        Ok(Self { statfs, dev })
+    }
+}
+
+/// Query the usage of every directory, in the order of the directories.
+///
+/// Directories whose statfs call does not return within the timeout are
+/// reported as [`Status::Stale`]. The call stays blocked on its worker
+/// thread, which is replaced so the pool keeps its size. Until it returns,
+/// the directory is reported as stale right away by later queries, like
+/// the repeated ones of `dfrs watch`, instead of blocking another thread.
+pub fn query_all(
+    dirs: &[String],
+    workers: usize,
+    timeout: Option<Duration>,
+) -> Vec<Result<Usage, Status>> {
+    run_pool(dirs, workers, timeout, &OUTSTANDING, Usage::query)
+}
+
+fn run_pool<T, F>(
+    dirs: &[String],
+    workers: usize,
+    timeout: Option<Duration>,
+    outstanding: &'static Outstanding,
+    query: F,
+) -> Vec<Result<T, Status>>
+where
+    T: Send + 'static,
+    F: Fn(&str) -> nix::Result<T> + Send + Sync + 'static,
+{
+    let mut results = {
+        let outstanding = outstanding.lock().unwrap_or_else(PoisonError::into_inner);
+        dirs.iter()
+            .map(|dir| {
+                outstanding.hung(dir).then(|| {
+                    debug!("statfs of {} is still outstanding", dir);
+                    Err(Status::Stale)
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+    let query = Arc::new(query);
+    let jobs: Jobs = Arc::new(Mutex::new(
+        dirs.iter()
+            .cloned()
+            .enumerate()
+            .filter(|(idx, _)| results[*idx].is_none())
+            .collect::<Vec<_>>()
+            .into_iter(),
+    ));
+    let mut pending = results.iter().filter(|result| result.is_none()).count();
+    let (tx, rx) = mpsc::channel();
+    for _ in 0..workers.clamp(1, pending.max(1)) {
+        spawn_worker(&jobs, &tx, outstanding, &query);
+    }
+
+    let mut started = vec![None; dirs.len()];
+    let mut tokens = vec![None; dirs.len()];
+    while pending > 0 {
+        let deadline = timeout.and_then(|timeout| {
+            started
+                .iter()
+                .zip(&results)
+                .filter(|(_, result)| result.is_none())
+                .filter_map(|(start, _)| *start)
+                .min()
+                .map(|start: Instant| start + timeout)
+        });
+        let message = deadline.map_or_else(
+            || rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            |deadline| rx.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+        );
+        match message {
+            Ok(Message::Started(idx, token, at)) => {
+                started[idx] = Some(at);
+                tokens[idx] = Some(token);
+            }
+            Ok(Message::Done(idx, result)) => {
+                if results[idx].is_none() {
+                    results[idx] = Some(result.map_err(Status::from_errno));
+                    pending -= 1;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                let now = Instant::now();
+                for idx in 0..dirs.len() {
+                    let expired = started[idx]
+                        .zip(timeout)
+                        .is_some_and(|(start, timeout)| now >= start + timeout);
+                    if results[idx].is_none() && expired {
+                        debug!("statfs of {} timed out", dirs[idx]);
+                        if let Some(token) = tokens[idx] {
+                            outstanding
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner)
+                                .time_out(token);
+                        }
+                        results[idx] = Some(Err(Status::Stale));
+                        pending -= 1;
+                        spawn_worker(&jobs, &tx, outstanding, &query);
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    results
+        .into_iter()
+        .map(|result| result.unwrap_or(Err(Status::Stale)))
+        .collect()
+}
+
+fn spawn_worker<T, F>(
+    jobs: &Jobs,
+    tx: &Sender<Message<T>>,
+    outstanding: &'static Outstanding,
+    query: &Arc<F>,
+) where
+    T: Send + 'static,
+    F: Fn(&str) -> nix::Result<T> + Send + Sync + 'static,
+{
+    let (jobs, tx, query) = (Arc::clone(jobs), tx.clone(), Arc::clone(query));
+    thread::spawn(move || loop {
+        let job = jobs.lock().unwrap_or_else(PoisonError::into_inner).next();
+        let Some((idx, dir)) = job else {
+            break;
+        };
+        let token = outstanding
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .start(dir.clone());
+        if tx
+            .send(Message::Started(idx, token, Instant::now()))
+            .is_err()
+        {
+            break;
+        }
+        let result = query(&dir);
+        outstanding
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .finish(token);
+        if tx.send(Message::Done(idx, result)).is_err() {
+            break;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::errno::Errno;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn dirs(dirs: &[&str]) -> Vec<String> {
+        dirs.iter().map(|dir| dir.to_string()).collect()
+    }
+
+    #[test]
+    fn query_all_keeps_order() {
+        let results = query_all(&dirs(&["/", "/no/such/dir", "/"]), 2, None);
+        assert!(results[0].is_ok());
+        assert_eq!(results[1], Err(Status::Failed(Errno::ENOENT)));
+        assert!(results[2].is_ok());
+    }
+
+    #[test]
+    fn run_pool_statuses() {
+        let query = |dir: &str| match dir {
+            "/slow" => {
+                thread::sleep(Duration::from_secs(1));
+                Ok(StatfsInfo::default())
+            }
+            "/stale" => Err(Errno::ESTALE),
+            "/broken" => Err(Errno::EIO),
+            _ => Ok(StatfsInfo::default()),
+        };
+        static OUTSTANDING: Outstanding = Mutex::new(Calls::new());
+        let results = run_pool(
+            &dirs(&["/slow", "/stale", "/broken", "/", "/slow", "/"]),
+            2,
+            Some(Duration::from_millis(50)),
+            &OUTSTANDING,
+            query,
+        );
+        assert_eq!(
+            results,
+            [
+                Err(Status::Stale),
+                Err(Status::StaleHandle),
+                Err(Status::IoError),
+                Ok(StatfsInfo::default()),
+                Err(Status::Stale),
+                Ok(StatfsInfo::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn outstanding_dirs_are_skipped() {
+        static OUTSTANDING: Outstanding = Mutex::new(Calls::new());
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let query = |_: &str| {
+            if CALLS.fetch_add(1, Ordering::SeqCst) == 0 {
+                thread::sleep(Duration::from_millis(300));
+            }
+            Ok(StatfsInfo::default())
+        };
+        let timeout = Some(Duration::from_millis(50));
+        let run = || run_pool(&dirs(&["/hung"]), 2, timeout, &OUTSTANDING, query);
+
+        assert_eq!(run(), [Err(Status::Stale)]);
+        assert_eq!(run(), [Err(Status::Stale)]);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(run(), [Ok(StatfsInfo::default())]);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn outstanding_dirs_queried_twice_stay_hung() {
+        static OUTSTANDING: Outstanding = Mutex::new(Calls::new());
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let query = |_: &str| {
+            if CALLS.fetch_add(1, Ordering::SeqCst) == 0 {
+                thread::sleep(Duration::from_millis(300));
+            }
+            Ok(StatfsInfo::default())
+        };
+        let timeout = Some(Duration::from_millis(50));
+
+        // the same mount point twice, like stacked mounts, only one hangs
+        let mut results = run_pool(&dirs(&["/hung", "/hung"]), 2, timeout, &OUTSTANDING, query);
+        results.sort_by_key(Result::is_ok);
+        assert_eq!(results, [Err(Status::Stale), Ok(StatfsInfo::default())]);
+
+        let results = run_pool(&dirs(&["/hung"]), 2, timeout, &OUTSTANDING, query);
+        assert_eq!(results, [Err(Status::Stale)]);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+    }
+}
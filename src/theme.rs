@@ -1,9 +1,11 @@
-use crate::args::ColumnType;
+use crate::column::ColumnType;
 use colored::*;
 
+#[derive(Debug, Clone)]
 pub struct Theme {
     pub char_bar_filled: char,
     pub char_bar_empty: char,
+    pub char_bar_reserved: char,
     pub char_bar_open: String,
     pub char_bar_close: String,
     pub threshold_usage_medium: f32,
@@ -14,6 +16,8 @@ pub struct Theme {
     pub color_usage_high: Option<Color>,
     pub color_usage_void: Option<Color>,
     pub bar_width: usize,
+    /// Color rows by the higher of the block and the inode usage
+    pub color_by_worse: bool,
     pub columns: Vec<ColumnType>,
 }
 
@@ -22,6 +26,7 @@ impl Theme {
         Self {
             char_bar_filled: named_char::HEAVY_BOX,
             char_bar_empty: named_char::HEAVY_DOUBLE_DASH,
+            char_bar_reserved: named_char::HEAVY_HORIZONTAL,
             char_bar_open: "".to_string(),
             char_bar_close: "".to_string(),
             threshold_usage_medium: 50.0,
@@ -32,6 +37,7 @@ impl Theme {
             color_usage_high: Some(Color::Red),
             color_usage_void: Some(Color::Blue),
             bar_width: 20,
+            color_by_worse: false,
             columns: vec![
                 ColumnType::Filesystem,
                 ColumnType::Type,
@@ -44,6 +50,23 @@ impl Theme {
             ],
         }
     }
+
+    /// Color of a used percentage according to the usage thresholds
+    pub fn usage_color(&self, percentage: Option<f32>) -> Color {
+        match percentage {
+            Some(p) if p >= self.threshold_usage_high => self.color_usage_high,
+            Some(p) if p >= self.threshold_usage_medium => self.color_usage_medium,
+            Some(_) => self.color_usage_low,
+            _ => self.color_usage_void,
+        }
+        .unwrap_or(Color::White)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[allow(dead_code)]
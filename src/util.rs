@@ -1,23 +1,328 @@
-use crate::mount::Mount;
-use crate::theme::Theme;
+use crate::errors::{Error, Result};
+use crate::mount::{Mount, StatfsInfo};
+use crate::theme::{named_char, Theme};
 
 use colored::*;
 use std::cmp;
+use std::collections::HashSet;
+use std::ffi::OsString;
 use std::fmt;
-use std::io::{self, stdout, Write};
+use std::fs::{self, File};
+use std::io::{self, stdout, BufWriter, Write};
 use std::path::Path;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Unit scaling of printed sizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Scale {
+    Base10,
+    Base2,
+    /// Plain numbers without any unit scaling
+    Raw,
+    /// Number of blocks of this many bytes, rounded up like GNU df
+    Blocks(u64),
+}
+
+const UNITS_BASE10: [&str; 9] = ["B", "k", "M", "G", "T", "P", "E", "Z", "Y"];
+const UNITS_BASE2: [&str; 9] = ["B", "K", "M", "G", "T", "P", "E", "Z", "Y"];
+const UNITS_IEC: [&str; 9] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB", "ZiB", "YiB"];
+
+/// How sizes and counts are printed
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NumberFormat {
+    pub scale: Scale,
+    /// Digits after the decimal point of scaled numbers
+    pub precision: usize,
+    /// Suffix numbers in powers of 1024 with KiB, MiB, ... instead of K, M, ...
+    pub iec: bool,
+    /// Separator grouping the thousands of unscaled numbers
+    pub thousands: Option<char>,
+    /// Numbers are counts, like inodes, rather than bytes
+    pub unitless: bool,
+    /// Round scaled numbers up like GNU df, to one decimal below 10 and to
+    /// whole numbers above, ignoring the precision
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub round_up: bool,
+}
+
+impl NumberFormat {
+    pub const fn new(scale: Scale) -> Self {
+        Self {
+            scale,
+            precision: 1,
+            iec: false,
+            thousands: None,
+            unitless: false,
+            round_up: false,
+        }
+    }
+
+    /// The format of counts, which carry no unit and are not divided by a
+    /// block size
+    pub fn counts(&self) -> Self {
+        let scale = match self.scale {
+            Scale::Blocks(_) => Scale::Raw,
+            scale => scale,
+        };
+        Self {
+            scale,
+            unitless: true,
+            ..self.clone()
+        }
+    }
+
+    pub const fn get_powers_of(&self) -> f64 {
+        match self.scale {
+            Scale::Base10 => 1000_f64,
+            Scale::Base2 => 1024_f64,
+            Scale::Raw | Scale::Blocks(_) => 1_f64,
+        }
+    }
+
+    pub fn format(&self, num: u64) -> String {
+        match self.scale {
+            Scale::Raw => group_thousands(num, self.thousands),
+            Scale::Blocks(size) => group_thousands(num.div_ceil(size), self.thousands),
+            _ if self.round_up => format_rounded_up(num, self.get_powers_of() as u64, false),
+            _ => self.format_scaled(num as f64),
+        }
+    }
+
+    fn format_scaled(&self, num: f64) -> String {
+        let units = match self.scale {
+            Scale::Base2 if self.iec => UNITS_IEC,
+            Scale::Base2 => UNITS_BASE2,
+            _ => UNITS_BASE10,
+        };
+        if self.unitless {
+            // counts below the base are exact and have no unit
+            let units = units.map(|unit| unit.trim_end_matches('B'));
+            if num < self.get_powers_of() {
+                return format!("{}", num.round());
+            }
+            return format_scaled(num, self.get_powers_of(), self.precision, &units);
+        }
+        format_scaled(num, self.get_powers_of(), self.precision, &units)
+    }
+}
 
 pub fn format_count(num: f64, delimiter: f64) -> String {
-    let units = ["B", "k", "M", "G", "T", "P", "E", "Z", "Y"];
+    let units = if delimiter == 1024_f64 {
+        UNITS_BASE2
+    } else {
+        UNITS_BASE10
+    };
+    format_scaled(num, delimiter, 1, &units)
+}
+
+fn format_scaled(num: f64, delimiter: f64, precision: usize, units: &[&str]) -> String {
     if num < 1_f64 {
         return format!("{}", num);
     }
     let exponent = cmp::min(num.log(delimiter).floor() as i32, (units.len() - 1) as i32);
-    let pretty_bytes = format!("{:.*}", 1, num / delimiter.powi(exponent));
+    let pretty_bytes = format!("{:.*}", precision, num / delimiter.powi(exponent));
     let unit = units[exponent as usize];
     format!("{}{}", pretty_bytes, unit)
 }
 
+/// Print a number like GNU's `human_readable`, autoscaled and rounded up.
+///
+/// There is one decimal below 10, none above and no unit below the base. A
+/// trailing `.0` is left out if `suppress_point_zero` is set.
+pub fn format_rounded_up(num: u64, base: u64, suppress_point_zero: bool) -> String {
+    const PREFIXES: [char; 10] = ['K', 'M', 'G', 'T', 'P', 'E', 'Z', 'Y', 'R', 'Q'];
+    let (num, base) = (u128::from(num), u128::from(base));
+    let mut exponent = 0;
+    let mut power = 1;
+    while num >= power * base && exponent < PREFIXES.len() {
+        power *= base;
+        exponent += 1;
+    }
+    if exponent == 0 {
+        return num.to_string();
+    }
+
+    let tenths = (num * 10).div_ceil(power);
+    let number = if tenths < 100 {
+        match tenths % 10 {
+            0 if suppress_point_zero => (tenths / 10).to_string(),
+            digit => format!("{}.{}", tenths / 10, digit),
+        }
+    } else if num.div_ceil(power) == base && exponent < PREFIXES.len() {
+        // rounding up reached the next power
+        exponent += 1;
+        if suppress_point_zero { "1" } else { "1.0" }.to_string()
+    } else {
+        num.div_ceil(power).to_string()
+    };
+
+    let prefix = match (base, exponent) {
+        (1000, 1) => 'k',
+        (_, exponent) => PREFIXES[exponent - 1],
+    };
+    format!("{}{}", number, prefix)
+}
+
+/// Group the digits of a number by thousands (e.g., `12,693,000`)
+pub fn group_thousands(num: u64, separator: Option<char>) -> String {
+    let digits = num.to_string();
+    let Some(separator) = separator else {
+        return digits;
+    };
+    let mut out = String::with_capacity(digits.len() * 4 / 3);
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+            out.push(separator);
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// Parse a GNU df block size (e.g., `K`, `1M`, `4KB` or `512`).
+///
+/// An optional number is followed by an optional unit, `K`, `M`, ... or
+/// `KiB`, `MiB`, ... in powers of 1024 and `KB`, `MB`, ... in powers of 1000.
+pub fn parse_block_size(size: &str) -> Result<u64> {
+    let invalid = || Error::InvalidSize(size.to_string());
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number = match number {
+        "" => 1,
+        number => number.parse::<u64>().map_err(|_| invalid())?,
+    };
+    let (unit, base) = unit.strip_suffix("iB").map_or_else(
+        || {
+            unit.strip_suffix('B')
+                .filter(|unit| !unit.is_empty())
+                .map_or((unit, 1024), |unit| (unit, 1000))
+        },
+        |unit| (unit, 1024_u64),
+    );
+    let exponent = match unit.to_ascii_uppercase().as_str() {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        "P" => 5,
+        "E" => 6,
+        _ => return Err(invalid()),
+    };
+    base.checked_pow(exponent)
+        .and_then(|unit| number.checked_mul(unit))
+        .filter(|size| *size > 0)
+        .ok_or_else(invalid)
+}
+
+/// Format a signed amount with an explicit sign for non-zero values
+pub fn format_signed(num: f64, delimiter: &NumberFormat) -> String {
+    let magnitude = match delimiter.scale {
+        Scale::Raw | Scale::Blocks(_) => delimiter.format(num.abs().round() as u64),
+        _ => delimiter.format_scaled(num.abs()),
+    };
+    if num.abs() < 0.5 {
+        "0".to_string()
+    } else if num < 0.0 {
+        format!("-{}", magnitude)
+    } else {
+        format!("+{}", magnitude)
+    }
+}
+
+/// Format seconds as the two most significant units (e.g., `3d4h`, `12m30s`)
+pub fn format_duration(secs: u64) -> String {
+    let units = [("d", 86400), ("h", 3600), ("m", 60), ("s", 1)];
+    let Some(first) = units.iter().position(|(_, unit)| secs >= *unit) else {
+        return "0s".to_string();
+    };
+    let (name, unit) = units[first];
+    let mut out = format!("{}{}", secs / unit, name);
+    if let Some((next_name, next_unit)) = units.get(first + 1) {
+        let rest = secs % unit / next_unit;
+        if rest > 0 {
+            out.push_str(&format!("{}{}", rest, next_name));
+        }
+    }
+    out
+}
+
+/// Format seconds since the Unix epoch as a UTC calendar date (e.g., `2024-03-01`)
+pub fn format_date(timestamp: u64) -> String {
+    // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (timestamp / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Current time in seconds since the Unix epoch
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Parse a size with an optional unit suffix in powers of 1024
+/// (e.g., `512`, `10k`, `1.5G`, `10GB` or `10GiB`).
+pub fn parse_size(size: &str) -> Result<u64> {
+    let invalid = || Error::InvalidSize(size.to_string());
+    let trimmed = size.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let number = number.parse::<f64>().map_err(|_| invalid())?;
+
+    let unit = unit.trim().to_ascii_uppercase();
+    let unit = unit
+        .strip_suffix("IB")
+        .or_else(|| unit.strip_suffix('B'))
+        .unwrap_or(&unit);
+    let exponent = match unit {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        "P" => 5,
+        "E" => 6,
+        _ => return Err(invalid()),
+    };
+    Ok((number * 1024_f64.powi(exponent)).round() as u64)
+}
+
+/// Shorten a string to `width` characters by replacing its middle with an
+/// ellipsis, keeping both the start and the more telling end of paths
+pub fn truncate_middle(s: &str, width: usize) -> String {
+    let len = s.chars().count();
+    if len <= width {
+        return s.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let tail = (width - 1) / 2;
+    let head = width - 1 - tail;
+    s.chars()
+        .take(head)
+        .chain(std::iter::once(named_char::ELLIPSIS))
+        .chain(s.chars().skip(len - tail))
+        .collect()
+}
+
 #[inline]
 pub fn format_percentage(percentage: Option<f32>) -> String {
     percentage.map_or_else(
@@ -32,8 +337,15 @@ pub fn format_percentage(percentage: Option<f32>) -> String {
     )
 }
 
-pub fn bar(width: usize, percentage: Option<f32>, theme: &Theme) -> String {
-    let fill_len_total = (percentage.unwrap_or(0.0) / 100.0 * width as f32).ceil() as usize;
+/// Usage bar of the used percentage, followed by a segment for the share
+/// of the total size reserved for the superuser
+pub fn bar(width: usize, percentage: Option<f32>, reserved: f32, theme: &Theme) -> String {
+    let reserved_len = std::cmp::min(width, (reserved / 100.0 * width as f32).round() as usize);
+    let width = width - reserved_len;
+    let fill_len_total = std::cmp::min(
+        width,
+        (percentage.unwrap_or(0.0) / 100.0 * width as f32).ceil() as usize,
+    );
     let fill_len_low = std::cmp::min(
         fill_len_total,
         (width as f32 * theme.threshold_usage_medium / 100.0).ceil() as usize,
@@ -70,10 +382,21 @@ pub fn bar(width: usize, percentage: Option<f32>, theme: &Theme) -> String {
         .to_string()
         .repeat(width - fill_len_total)
         .color(color_empty);
+    let reserved = theme
+        .char_bar_reserved
+        .to_string()
+        .repeat(reserved_len)
+        .color(theme.color_usage_void.unwrap_or(Color::Blue));
 
     format!(
-        "{}{}{}{}{}{}",
-        theme.char_bar_open, fill_low, fill_medium, fill_high, empty, theme.char_bar_close
+        "{}{}{}{}{}{}{}",
+        theme.char_bar_open,
+        fill_low,
+        fill_medium,
+        fill_high,
+        empty,
+        reserved,
+        theme.char_bar_close
     )
 }
 
@@ -92,10 +415,10 @@ pub fn lvm_alias(device: &str) -> Option<String> {
 }
 
 #[inline]
-pub fn get_best_mount_match<'a>(path: &Path, mnts: &'a [Mount]) -> Option<&'a Mount> {
+pub fn get_best_mount_match<'a, T: AsRef<Mount>>(path: &Path, mnts: &'a [T]) -> Option<&'a T> {
     let scores = mnts
         .iter()
-        .map(|mnt| (calculate_path_match_score(path, mnt), mnt));
+        .map(|mnt| (calculate_path_match_score(path, mnt.as_ref()), mnt));
     let best = scores.max_by_key(|x| x.0)?;
     Some(best.1)
 }
@@ -117,25 +440,110 @@ pub fn cmp_by_capacity_and_dir_name(a: &Mount, b: &Mount) -> cmp::Ordering {
         .then(a.mnt_dir.cmp(&b.mnt_dir))
 }
 
-#[inline]
-pub fn mnt_matches_filter(mnt: &Mount, filter: &str) -> bool {
-    filter.strip_suffix('*').map_or_else(
-        || mnt.mnt_fsname == filter,
-        |start| mnt.mnt_fsname.starts_with(start),
-    )
-}
-
-#[inline]
+/// Sum up the usage, counting every file system mounted more than once a
+/// single time. The statfs numbers are summed up in bytes, so the inode
+/// usage of the total is known as well.
 pub fn calc_total(mnts: &[Mount]) -> Mount {
     let mut total = Mount::named("total".to_string());
 
-    total.free = mnts.iter().map(|mnt| mnt.free).sum();
-    total.used = mnts.iter().map(|mnt| mnt.used).sum();
-    total.capacity = mnts.iter().map(|mnt| mnt.capacity).sum();
+    let mut seen = HashSet::new();
+    for mnt in mnts {
+        if mnt.fs_key().is_some_and(|key| !seen.insert(key)) {
+            continue;
+        }
+        total.available += mnt.available;
+        total.reserved += mnt.reserved;
+        total.used += mnt.used;
+        total.capacity += mnt.capacity;
+        if let Some(stat) = mnt.statfs {
+            let sum = total.statfs.get_or_insert_with(|| StatfsInfo {
+                block_size: 1,
+                ..StatfsInfo::default()
+            });
+            sum.blocks += stat.blocks * stat.block_size;
+            sum.blocks_free += stat.blocks_free * stat.block_size;
+            sum.blocks_available += stat.blocks_available * stat.block_size;
+            sum.files += stat.files;
+            sum.files_free += stat.files_free;
+        }
+    }
 
     total
 }
 
+/// Collapse mounts of the same file system into the first of them, keeping
+/// the other mount points
+pub fn dedupe<T: AsRef<Mount> + AsMut<Mount>>(mnts: Vec<T>) -> Vec<T> {
+    let key = |mnt: &Mount| {
+        mnt.fs_key()
+            .map(|(host, dev)| (host.map(str::to_string), dev))
+    };
+    dedupe_by(mnts, key, |_, _| false)
+}
+
+/// Collapse mounts with the same key into one, keeping the other mount points.
+///
+/// The collapsed mount stays at the position of the first one. A later mount takes the place
+/// of the collapsed one if `replaces(mount, collapsed)` holds.
+pub fn dedupe_by<T, K, F, R>(mnts: Vec<T>, key: F, replaces: R) -> Vec<T>
+where
+    T: AsRef<Mount> + AsMut<Mount>,
+    K: PartialEq,
+    F: Fn(&Mount) -> Option<K>,
+    R: Fn(&Mount, &Mount) -> bool,
+{
+    let mut out: Vec<(Option<K>, T)> = Vec::with_capacity(mnts.len());
+    for mnt in mnts {
+        let key = key(mnt.as_ref());
+        let first = key
+            .as_ref()
+            .and_then(|key| out.iter_mut().find(|(k, _)| k.as_ref() == Some(key)));
+        match first {
+            Some((_, first)) => {
+                let mut other = if replaces(mnt.as_ref(), first.as_ref()) {
+                    std::mem::replace(first, mnt)
+                } else {
+                    mnt
+                };
+                let (first, other) = (first.as_mut(), other.as_mut());
+                first.other_dirs.push(std::mem::take(&mut other.mnt_dir));
+                first.other_dirs.append(&mut other.other_dirs);
+            }
+            None => out.push((key, mnt)),
+        }
+    }
+    out.into_iter().map(|(_, mnt)| mnt).collect()
+}
+
+/// Write to a temporary file next to `path` and rename it into place, so
+/// readers never observe a partially written file.
+pub fn write_atomic<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> io::Result<()>,
+{
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(".{}.tmp", process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = File::create(&tmp_path).and_then(|file| {
+        let mut w = BufWriter::new(file);
+        write(&mut w)?;
+        let file = w.into_inner().map_err(io::IntoInnerError::into_error)?;
+        file.sync_all()
+    });
+    match result.and_then(|_| fs::rename(&tmp_path, path)) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            let _ = fs::remove_file(&tmp_path);
+            Err(err)
+        }
+    }
+}
+
 #[inline]
 pub fn try_print(args: fmt::Arguments) -> io::Result<()> {
     stdout().write_fmt(args)
@@ -148,8 +556,8 @@ macro_rules! try_print {
 
 #[macro_export]
 macro_rules! try_println {
-    ($fmt:expr) => (try_print!(concat!($fmt, "\n")));
-    ($fmt:expr, $($arg:tt)*) => (try_print!(concat!($fmt, "\n"), $($arg)*));
+    ($fmt:expr) => ($crate::try_print!(concat!($fmt, "\n")));
+    ($fmt:expr, $($arg:tt)*) => ($crate::try_print!(concat!($fmt, "\n"), $($arg)*));
 }
 
 #[cfg(test)]
@@ -181,6 +589,129 @@ mod tests {
         assert_eq!(s, "2097152.0Y");
     }
 
+    #[test]
+    fn number_format_raw() {
+        let s = NumberFormat::new(Scale::Raw).format(12693000);
+        assert_eq!(s, "12693000");
+    }
+
+    #[test]
+    fn number_format_base10() {
+        let s = NumberFormat::new(Scale::Base10).format(12693000);
+        assert_eq!(s, "12.7M");
+    }
+
+    #[test]
+    fn number_format_options() {
+        let mut format = NumberFormat::new(Scale::Base2);
+        assert_eq!(format.format(1536), "1.5K");
+        format.iec = true;
+        format.precision = 2;
+        assert_eq!(format.format(12693000), "12.10MiB");
+        assert_eq!(format.format(12), "12.00B");
+
+        let counts = NumberFormat::new(Scale::Base10).counts();
+        assert_eq!(counts.format(999), "999");
+        assert_eq!(counts.format(452_700), "452.7k");
+
+        let mut blocks = NumberFormat::new(Scale::Blocks(1024));
+        assert_eq!(blocks.format(1025), "2");
+        blocks.thousands = Some(',');
+        assert_eq!(blocks.format(1_234_567 * 1024), "1,234,567");
+        assert_eq!(blocks.counts().format(1000), "1,000");
+        assert_eq!(blocks.counts().scale, Scale::Raw);
+    }
+
+    #[test]
+    fn group_thousands_digits() {
+        assert_eq!(group_thousands(0, Some(',')), "0");
+        assert_eq!(group_thousands(999, Some(',')), "999");
+        assert_eq!(group_thousands(1000, Some('.')), "1.000");
+        assert_eq!(group_thousands(12693000, Some(' ')), "12 693 000");
+        assert_eq!(group_thousands(12693000, None), "12693000");
+    }
+
+    #[test]
+    fn parse_block_size_units() {
+        assert_eq!(parse_block_size("512").unwrap(), 512);
+        assert_eq!(parse_block_size("K").unwrap(), 1024);
+        assert_eq!(parse_block_size("1M").unwrap(), 1024 * 1024);
+        assert_eq!(parse_block_size("4KB").unwrap(), 4000);
+        assert_eq!(parse_block_size("2GiB").unwrap(), 2 << 30);
+        assert!(parse_block_size("0").is_err());
+        assert!(parse_block_size("1.5M").is_err());
+        assert!(parse_block_size("B").is_err());
+        assert!(parse_block_size("X").is_err());
+    }
+
+    #[test]
+    fn format_signed_values() {
+        assert_eq!(format_signed(0.0, &NumberFormat::new(Scale::Base2)), "0");
+        assert_eq!(
+            format_signed(12693000.0, &NumberFormat::new(Scale::Base2)),
+            "+12.1M"
+        );
+        assert_eq!(
+            format_signed(-2048.0, &NumberFormat::new(Scale::Base2)),
+            "-2.0K"
+        );
+        assert_eq!(
+            format_signed(-2048.0, &NumberFormat::new(Scale::Raw)),
+            "-2048"
+        );
+    }
+
+    #[test]
+    fn format_duration_units() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(59), "59s");
+        assert_eq!(format_duration(750), "12m30s");
+        assert_eq!(format_duration(3600), "1h");
+        assert_eq!(format_duration(273600), "3d4h");
+    }
+
+    #[test]
+    fn format_date_epoch_and_leap_day() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(1_709_337_599), "2024-03-01");
+        assert_eq!(format_date(4_102_444_800), "2100-01-01");
+    }
+
+    #[test]
+    fn parse_size_plain() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("512B").unwrap(), 512);
+    }
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("10k").unwrap(), 10 * 1024);
+        assert_eq!(parse_size("10G").unwrap(), 10 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("10GB").unwrap(), 10 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("10GiB").unwrap(), 10 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("1.5M").unwrap(), 1536 * 1024);
+    }
+
+    #[test]
+    fn parse_size_invalid() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("10%").is_err());
+    }
+
+    #[test]
+    fn truncate_middle_keeps_both_ends() {
+        assert_eq!(truncate_middle("/srv", 10), "/srv");
+        assert_eq!(
+            truncate_middle("/var/lib/kubelet/pods/1234/volumes", 16),
+            "/var/lib…volumes"
+        );
+        assert_eq!(truncate_middle("/dev/sda1", 1), "…");
+        assert_eq!(truncate_middle("/dev/sda1", 0), "");
+    }
+
     #[test]
     fn format_percentage_zero() {
         let s = format_percentage(Option::Some(0f32));
@@ -205,6 +736,18 @@ mod tests {
         assert_eq!(s, "     -");
     }
 
+    #[test]
+    fn bar_reserved_segment() {
+        colored::control::set_override(false);
+        let mut theme = Theme::new();
+        theme.char_bar_filled = '#';
+        theme.char_bar_empty = '.';
+        theme.char_bar_reserved = 'r';
+        assert_eq!(bar(10, Some(50.0), 20.0, &theme), "####....rr");
+        assert_eq!(bar(10, Some(100.0), 0.0, &theme), "##########");
+        assert_eq!(bar(10, None, 0.0, &theme), "..........");
+    }
+
     #[test]
     fn lvm_alias_none() {
         let s = lvm_alias("/dev/mapper/crypto");
@@ -352,29 +895,147 @@ mod tests {
         assert_eq!(ord, cmp::Ordering::Equal);
     }
 
+    #[test]
+    fn write_atomic_replaces_file() {
+        let dir = std::env::temp_dir().join(format!("dfrs-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dfrs.prom");
+        fs::write(&path, "old").unwrap();
+
+        write_atomic(&path, |w| w.write_all(b"new")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+
+        let err = write_atomic(&path, |_| Err(io::Error::other("fail")));
+        assert!(err.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn calc_total_simple() {
         let mut mnt1 = Mount::named("foo".into());
-        mnt1.free = 123;
+        mnt1.available = 123;
         mnt1.used = 456;
         mnt1.capacity = 123 + 456;
         let mut mnt2 = Mount::named("bar".into());
-        mnt2.free = 678;
+        mnt2.available = 678;
         mnt2.used = 9123;
         mnt2.capacity = 678 + 9123;
         let mut mnt3 = Mount::named("fizz".into());
-        mnt3.free = 4567;
+        mnt3.available = 4567;
         mnt3.used = 0;
         mnt3.capacity = 4567;
         let mut mnt4 = Mount::named("buzz".into());
-        mnt4.free = 0;
+        mnt4.available = 0;
         mnt4.used = 890123;
         mnt4.capacity = 890123;
 
         let total = calc_total(&[mnt1, mnt2, mnt3, mnt4]);
         assert_eq!(total.mnt_fsname, "total");
-        assert_eq!(total.free, 5368);
+        assert_eq!(total.available, 5368);
         assert_eq!(total.used, 899702);
         assert_eq!(total.capacity, 5368 + 899702);
     }
+
+    fn device_mount(dir: &str, dev: (u32, u32), host: Option<&str>) -> Mount {
+        let mut mnt = Mount::named("/dev/sda1".into());
+        mnt.mnt_dir = dir.to_string();
+        mnt.mnt_dev = Some(dev);
+        mnt.host = host.map(str::to_string);
+        mnt.capacity = 100;
+        mnt.used = 40;
+        mnt.available = 60;
+        mnt
+    }
+
+    #[test]
+    fn calc_total_counts_file_systems_once() {
+        let mnts = [
+            device_mount("/", (8, 1), None),
+            device_mount("/var/lib/docker/a", (8, 1), None),
+            device_mount("/srv", (8, 2), None),
+            Mount::named("none".into()),
+        ];
+        let total = calc_total(&mnts);
+        assert_eq!(total.capacity, 200);
+        assert_eq!(total.used, 80);
+    }
+
+    #[test]
+    fn calc_total_sums_statfs_in_bytes() {
+        let mut mnts = [
+            device_mount("/", (8, 1), None),
+            device_mount("/srv", (8, 2), None),
+        ];
+        for (mnt, block_size) in mnts.iter_mut().zip([1024, 4096]) {
+            mnt.statfs = Some(StatfsInfo {
+                block_size,
+                blocks: 10,
+                blocks_free: 4,
+                blocks_available: 2,
+                files: 100,
+                files_free: 60,
+            });
+        }
+        let stat = calc_total(&mnts).statfs.unwrap();
+        assert_eq!((stat.block_size, stat.blocks), (1, 10 * 5120));
+        assert_eq!(
+            (stat.blocks_free, stat.blocks_available),
+            (4 * 5120, 2 * 5120)
+        );
+        assert_eq!((stat.files, stat.files_free), (200, 120));
+        assert_eq!(calc_total(&[Mount::named("none".into())]).statfs, None);
+    }
+
+    #[test]
+    fn format_rounded_up_like_gnu() {
+        assert_eq!(format_rounded_up(0, 1024, false), "0");
+        assert_eq!(format_rounded_up(1023, 1024, false), "1023");
+        assert_eq!(format_rounded_up(1024, 1024, false), "1.0K");
+        assert_eq!(format_rounded_up(1024, 1024, true), "1K");
+        assert_eq!(format_rounded_up(1025, 1024, false), "1.1K");
+        assert_eq!(format_rounded_up(10 * 1024 - 1, 1024, false), "10K");
+        assert_eq!(format_rounded_up(1024 * 1024 - 1, 1024, false), "1.0M");
+        assert_eq!(format_rounded_up(3_140_218_880, 1024, false), "3.0G");
+        assert_eq!(format_rounded_up(270_553_174_016, 1000, false), "271G");
+        assert_eq!(format_rounded_up(1500, 1000, false), "1.5k");
+
+        let format = NumberFormat {
+            round_up: true,
+            ..NumberFormat::new(Scale::Base2)
+        };
+        assert_eq!(format.format(1025), "1.1K");
+        assert_eq!(format.counts().format(1023), "1023");
+    }
+
+    #[test]
+    fn dedupe_by_replaces() {
+        let mnts = vec![
+            device_mount("/srv/a", (8, 1), None),
+            device_mount("/srv", (8, 2), None),
+            device_mount("/", (8, 1), None),
+        ];
+        let shorter = |mnt: &Mount, seen: &Mount| mnt.mnt_dir.len() < seen.mnt_dir.len();
+        let mnts = dedupe_by(mnts, |mnt| mnt.mnt_dev, shorter);
+        assert_eq!(mnts.len(), 2);
+        assert_eq!(mnts[0].dirs_formatted(), "/, /srv/a");
+        assert_eq!(mnts[1].dirs_formatted(), "/srv");
+    }
+
+    #[test]
+    fn dedupe_collapses_mount_points() {
+        let mnts = vec![
+            device_mount("/", (8, 1), None),
+            device_mount("/srv", (8, 2), None),
+            device_mount("/var/lib/docker/a", (8, 1), None),
+            device_mount("/", (8, 1), Some("other")),
+        ];
+        let mnts = dedupe(mnts);
+        assert_eq!(mnts.len(), 3);
+        assert_eq!(mnts[0].dirs_formatted(), "/, /var/lib/docker/a");
+        assert_eq!(mnts[1].dirs_formatted(), "/srv");
+        assert_eq!(mnts[2].host.as_deref(), Some("other"));
+    }
 }
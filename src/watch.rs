@@ -0,0 +1,233 @@
+use dfrs::mount::Trend;
+use dfrs::Mount;
+
+use anyhow::Result;
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, stdout, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Number of samples the rate and time-to-full estimation is based on
+const RATE_WINDOW: usize = 10;
+
+const ENTER_ALTERNATE_SCREEN: &str = "\x1b[?1049h\x1b[?25l";
+const LEAVE_ALTERNATE_SCREEN: &str = "\x1b[?25h\x1b[?1049l";
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_stop(_: nix::libc::c_int) {
+    STOP.store(true, Ordering::SeqCst);
+}
+
+fn install_signal_handlers() -> nix::Result<()> {
+    let action = SigAction::new(
+        SigHandler::Handler(request_stop),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    for signal in [Signal::SIGINT, Signal::SIGTERM] {
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+        unsafe { sigaction(signal, &action) }?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    elapsed: f64,
+    used: u64,
+}
+
+/// Keeps the recent samples of every mount to derive their trends.
+#[derive(Debug, Default)]
+pub struct Sampler {
+    history: HashMap<String, VecDeque<Sample>>,
+}
+
+impl Sampler {
+    fn key(mnt: &Mount) -> String {
+        format!("{}\0{}", mnt.mnt_fsname, mnt.mnt_dir)
+    }
+
+    /// Record a sample taken `elapsed` seconds after the start and fill the
+    /// trend of each mount from its previous samples.
+    pub fn update(&mut self, mnts: &mut [Mount], elapsed: f64) {
+        let mut history = HashMap::with_capacity(mnts.len());
+        for mnt in mnts.iter_mut() {
+            let key = Self::key(mnt);
+            let mut samples = self.history.remove(&key).unwrap_or_default();
+
+            let previous = samples.back().copied();
+            samples.push_back(Sample {
+                elapsed,
+                used: mnt.used,
+            });
+            while samples.len() > RATE_WINDOW {
+                samples.pop_front();
+            }
+
+            let oldest = samples.front().copied();
+            mnt.trend = previous.zip(oldest).map(|(previous, oldest)| {
+                let duration = elapsed - oldest.elapsed;
+                let rate = if duration > 0.0 {
+                    (mnt.used as f64 - oldest.used as f64) / duration
+                } else {
+                    0.0
+                };
+                Trend {
+                    delta: mnt.used as i64 - previous.used as i64,
+                    rate,
+                    time_to_full: (rate > 0.0)
+                        .then(|| (mnt.available as f64 / rate).round() as u64),
+                }
+            });
+            history.insert(key, samples);
+        }
+        self.history = history;
+    }
+}
+
+/// Restores the terminal when watching ends, including on errors
+struct AlternateScreen;
+
+impl AlternateScreen {
+    fn enter() -> io::Result<Self> {
+        let mut out = stdout().lock();
+        out.write_all(ENTER_ALTERNATE_SCREEN.as_bytes())?;
+        out.flush()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for AlternateScreen {
+    fn drop(&mut self) {
+        let mut out = stdout().lock();
+        let _ = out.write_all(LEAVE_ALTERNATE_SCREEN.as_bytes());
+        let _ = out.flush();
+    }
+}
+
+/// Sleep until the deadline, returning false if a stop was requested
+fn sleep_until(deadline: Instant) -> bool {
+    while !STOP.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        thread::sleep((deadline - now).min(Duration::from_millis(100)));
+    }
+    false
+}
+
+/// Repeatedly sample the mounts and redraw them until interrupted
+pub fn watch<S, R>(interval: Duration, mut sample: S, render: R) -> Result<()>
+where
+    S: FnMut() -> Result<Vec<Mount>>,
+    R: Fn(&mut Vec<u8>, &[Mount]) -> io::Result<()>,
+{
+    install_signal_handlers()?;
+    let is_terminal = stdout().is_terminal();
+    let _screen = is_terminal.then(AlternateScreen::enter).transpose()?;
+
+    let mut sampler = Sampler::default();
+    let start = Instant::now();
+    let mut deadline = start;
+    while sleep_until(deadline) {
+        let mut mnts = sample()?;
+        sampler.update(&mut mnts, start.elapsed().as_secs_f64());
+
+        let mut frame = Vec::new();
+        if is_terminal {
+            frame.extend_from_slice(CLEAR_SCREEN.as_bytes());
+        }
+        writeln!(frame, "Every {:?}: dfrs\n", interval)?;
+        render(&mut frame, &mnts)?;
+
+        let mut out = stdout().lock();
+        if let Err(err) = out.write_all(&frame).and_then(|_| out.flush()) {
+            if err.kind() == io::ErrorKind::BrokenPipe {
+                break;
+            }
+            return Err(err.into());
+        }
+
+        deadline += interval;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(used: u64, free: u64) -> Mount {
+        let mut mnt = Mount::named("/dev/sda1".into());
+        mnt.mnt_dir = "/var".to_string();
+        mnt.used = used;
+        mnt.available = free;
+        mnt.capacity = used + free;
+        mnt
+    }
+
+    #[test]
+    fn sampler_first_sample_has_no_trend() {
+        let mut sampler = Sampler::default();
+        let mut mnts = [mount(100, 900)];
+        sampler.update(&mut mnts, 0.0);
+        assert!(mnts[0].trend.is_none());
+    }
+
+    #[test]
+    fn sampler_delta_and_rate() {
+        let mut sampler = Sampler::default();
+        sampler.update(&mut [mount(100, 900)], 0.0);
+        sampler.update(&mut [mount(200, 800)], 2.0);
+        let mut mnts = [mount(400, 600)];
+        sampler.update(&mut mnts, 4.0);
+
+        let trend = mnts[0].trend.unwrap();
+        assert_eq!(trend.delta, 200);
+        assert_eq!(trend.rate, 75.0);
+        assert_eq!(trend.time_to_full, Some(8));
+    }
+
+    #[test]
+    fn sampler_shrinking_usage() {
+        let mut sampler = Sampler::default();
+        sampler.update(&mut [mount(400, 600)], 0.0);
+        let mut mnts = [mount(300, 700)];
+        sampler.update(&mut mnts, 1.0);
+
+        let trend = mnts[0].trend.unwrap();
+        assert_eq!(trend.delta, -100);
+        assert_eq!(trend.rate, -100.0);
+        assert_eq!(trend.time_to_full, None);
+    }
+
+    #[test]
+    fn sampler_rate_window() {
+        let mut sampler = Sampler::default();
+        for i in 0..RATE_WINDOW as u64 {
+            sampler.update(&mut [mount(1000 * i, 1_000_000)], i as f64);
+        }
+        // a burst long ago no longer counts once it left the window
+        let mut mnts = [mount(1000 * (RATE_WINDOW as u64 - 1), 1_000_000)];
+        sampler.update(&mut mnts, RATE_WINDOW as f64);
+        let trend = mnts[0].trend.unwrap();
+        assert_eq!(trend.delta, 0);
+        assert_eq!(trend.rate, 8000.0 / 9.0);
+    }
+
+    #[test]
+    fn sampler_forgets_vanished_mounts() {
+        let mut sampler = Sampler::default();
+        sampler.update(&mut [mount(100, 900)], 0.0);
+        sampler.update(&mut [], 1.0);
+        let mut mnts = [mount(200, 800)];
+        sampler.update(&mut mnts, 2.0);
+        assert!(mnts[0].trend.is_none());
+    }
+}